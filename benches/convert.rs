@@ -0,0 +1,105 @@
+//! Conversion benchmarks.
+//!
+//! Runs on bn-khipro if `M17N_BENCH_MIM` points at a copy of
+//! `bn-khipro.mim`, and otherwise on `benches/fixtures/bn-bench.mim`, a
+//! synthetic input method of the same layout. The input is the romanized
+//! side of `src/khipro_test_cases.json`. Results are recorded in
+//! `benches/README.md`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::{ImInfo, load_im_info};
+use m17n_mim_rs::lisp_parser::parse_mim;
+use m17n_mim_rs::snapshot::{load_snapshot, write_snapshot};
+use serde_json::Value;
+use std::hint::black_box;
+use std::{env, fs};
+
+fn load_mim() -> String {
+    let path =
+        env::var("M17N_BENCH_MIM").unwrap_or_else(|_| "benches/fixtures/bn-bench.mim".to_string());
+    fs::read_to_string(&path).unwrap_or_else(|err| panic!("failed to read {}: {}", path, err))
+}
+
+fn load_input() -> String {
+    let cases: Value =
+        serde_json::from_str(&fs::read_to_string("src/khipro_test_cases.json").unwrap()).unwrap();
+    let words: Vec<&str> = cases
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|case| case[0].as_str())
+        .collect();
+    words.join(" ")
+}
+
+/// The previous lookup: one `String` and one `fst.get` per prefix length,
+/// capped at 10 keys.
+fn naive_longest_match(im_info: &ImInfo, keys: &[char]) -> usize {
+    let mut best = 0;
+    for map in im_info.maps.values() {
+        for len in 1..=keys.len().min(10) {
+            let key_str: String = keys[..len].iter().collect();
+            if map.fst.get(key_str.as_bytes()).is_some() && len > best {
+                best = len;
+            }
+        }
+    }
+    best
+}
+
+fn walking_longest_match(im_info: &ImInfo, keys: &[char]) -> usize {
+    im_info
+        .maps
+        .values()
+        .filter_map(|map| map.longest_match(keys))
+        .map(|(len, _)| len)
+        .max()
+        .unwrap_or(0)
+}
+
+fn bench_longest_match(c: &mut Criterion) {
+    let im_info = load_im_info(parse_mim(&load_mim()).unwrap());
+    let input: Vec<char> = load_input().chars().collect();
+
+    let mut group = c.benchmark_group("longest_match");
+    group.bench_function("prefix_strings", |b| {
+        b.iter(|| {
+            (0..input.len())
+                .map(|i| naive_longest_match(&im_info, black_box(&input[i..])))
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("fst_walk", |b| {
+        b.iter(|| {
+            (0..input.len())
+                .map(|i| walking_longest_match(&im_info, black_box(&input[i..])))
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+fn bench_convert(c: &mut Criterion) {
+    let mim = M17nMim::new(&load_mim());
+    let input = load_input();
+
+    c.bench_function("convert", |b| b.iter(|| mim.convert(black_box(&input))));
+}
+
+fn bench_load(c: &mut Criterion) {
+    let mim = load_mim();
+    let snapshot = write_snapshot(&load_im_info(parse_mim(&mim).unwrap()));
+
+    let mut group = c.benchmark_group("load");
+    group.bench_function("parse", |b| {
+        b.iter(|| load_im_info(parse_mim(black_box(&mim)).unwrap()))
+    });
+    group.bench_function("snapshot", |b| {
+        b.iter(|| load_snapshot(black_box(&snapshot)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_longest_match, bench_convert, bench_load);
+criterion_main!(benches);
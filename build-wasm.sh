@@ -4,12 +4,16 @@
 # Based on: https://fourteenscrews.com/essays/look-ma-no-wasm-pack/
 # Requires: cargo, wasm-bindgen-cli (cargo install wasm-bindgen-cli)
 # Optional: wasm-opt (from Binaryen)
+# Also checks that the no_std core builds for a bare-metal target, where
+# std is not linked (skip with --skip-no-std-check)
 set -euo pipefail
 
 BUILD_TYPE="release"
 TARGET="wasm32-unknown-unknown"
 OUTDIR="pkg"
 SKIP_WASM_OPT=0
+SKIP_NO_STD_CHECK=0
+NO_STD_TARGET="thumbv7em-none-eabi"
 
 while (( "$#" )); do
   case "$1" in
@@ -18,6 +22,7 @@ while (( "$#" )); do
     --target) TARGET="$2"; shift 2 ;;
     --out) OUTDIR="$2"; shift 2 ;;
     --skip-wasm-opt) SKIP_WASM_OPT=1; shift ;;
+    --skip-no-std-check) SKIP_NO_STD_CHECK=1; shift ;;
     *) echo "Unknown arg: $1"; exit 1 ;;
   esac
 done
@@ -28,6 +33,12 @@ err() { echo -e "\033[1;31merror:\033[0m $*" >&2; }
 # Step 1: check environment
 [ -f Cargo.toml ] || { err "Run this from your crate root"; exit 1; }
 
+if (( ! SKIP_NO_STD_CHECK )); then
+  log "Checking the no_std build on $NO_STD_TARGET..."
+  rustup target add "$NO_STD_TARGET" >/dev/null 2>&1 || true
+  cargo check --lib --no-default-features --target "$NO_STD_TARGET"
+fi
+
 log "Checking for wasm target..."
 rustup target add "$TARGET" >/dev/null 2>&1 || true
 
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::load_im_info_from_reader;
+use m17n_mim_rs::lisp_parser::ParseLimits;
+
+fuzz_target!(|data: &[u8]| {
+    // The reader-based loader must reject or accept any bytes without panicking
+    let _ = load_im_info_from_reader(data);
+
+    // Split the input into a .mim text and a string to convert with it
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    let (mim_str, text) = input.split_once('\0').unwrap_or((input, "abc"));
+    if let Ok(mim) = M17nMim::with_limits(mim_str, &ParseLimits::default()) {
+        let _ = mim.convert(text);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use m17n_mim_rs::lisp_parser::{ParseLimits, parse_mim_with_limits};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let _ = parse_mim_with_limits(input, &ParseLimits::default());
+    }
+});
@@ -0,0 +1,97 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Compile-time embedding of .mim input methods.
+
+use m17n_mim_rs::im_info::load_im_info;
+use m17n_mim_rs::lisp_parser::{ParseLimits, parse_mim_strict};
+use m17n_mim_rs::snapshot::{SNAPSHOT_VERSION, write_snapshot};
+use proc_macro::TokenStream;
+use proc_macro2::Literal;
+use quote::quote;
+use std::path::PathBuf;
+use syn::{LitStr, parse_macro_input};
+
+/// Parse a .mim file at compile time and expand to its
+/// [`ImInfo`](m17n_mim_rs::im_info::ImInfo), as a `&'static Arc<ImInfo>`.
+///
+/// The path is relative to the root of the crate using the macro (its
+/// `CARGO_MANIFEST_DIR`). The file is parsed strictly, so unbalanced
+/// parentheses, unterminated strings and exceeded parse limits are compile
+/// errors. The input method is embedded as a snapshot whose FSTs are used
+/// in place, so the expansion does no parsing or FST building at run time.
+///
+/// An [`ImInfo`] keeps its names, rules and states in `String`s, `Vec`s and
+/// a `BTreeMap`, which cannot be built in a `const`, so the snapshot is
+/// decoded into a `static` the first time the expansion is evaluated, and
+/// later evaluations give the same value. The FSTs are not read through to
+/// be checked, since the macro wrote them. A macro built against an
+/// `m17n_mim_rs` whose snapshot version differs from the one the program
+/// uses is a compile error.
+///
+/// [`ImInfo`]: m17n_mim_rs::im_info::ImInfo
+///
+/// ```
+/// use m17n_mim_macros::include_mim;
+/// use m17n_mim_rs::M17nMim;
+/// use std::sync::Arc;
+///
+/// let mim = M17nMim::from_im_info(Arc::clone(include_mim!("../tests/fixtures/bn-sample.mim")));
+/// assert_eq!(mim.convert("kaki"), "কাকি");
+/// ```
+///
+/// A broken .mim file fails the build:
+///
+/// ```compile_fail
+/// let im_info = m17n_mim_macros::include_mim!("tests/fixtures/unclosed.mim");
+/// ```
+#[proc_macro]
+pub fn include_mim(input: TokenStream) -> TokenStream {
+    let path_lit = parse_macro_input!(input as LitStr);
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = PathBuf::from(manifest_dir).join(path_lit.value());
+
+    let mim = match std::fs::read_to_string(&path) {
+        Ok(mim) => mim,
+        Err(err) => {
+            let message = format!("cannot read {}: {}", path.display(), err);
+            return syn::Error::new(path_lit.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+    let parsed = match parse_mim_strict(&mim, &ParseLimits::default()) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            let message = format!("{}: {}", path.display(), err);
+            return syn::Error::new(path_lit.span(), message)
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    let snapshot = Literal::byte_string(&write_snapshot(&load_im_info(parsed)));
+    let path = path.to_string_lossy();
+    quote! {
+        {
+            // Rebuild when the .mim file changes
+            const _: &[u8] = include_bytes!(#path);
+            const _: () = assert!(
+                ::m17n_mim_rs::snapshot::SNAPSHOT_VERSION == #SNAPSHOT_VERSION,
+                "include_mim! was built against an m17n_mim_rs with another snapshot version"
+            );
+            static SNAPSHOT: &[u8] = #snapshot;
+            static IM_INFO: ::std::sync::LazyLock<
+                ::std::sync::Arc<::m17n_mim_rs::im_info::ImInfo>,
+            > = ::std::sync::LazyLock::new(|| {
+                ::std::sync::Arc::new(
+                    ::m17n_mim_rs::snapshot::load_snapshot_static_trusted(SNAPSHOT)
+                        .expect("include_mim! reads back the snapshot it wrote"),
+                )
+            });
+            &*IM_INFO
+        }
+    }
+    .into()
+}
@@ -0,0 +1,33 @@
+use m17n_mim_macros::include_mim;
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::{FstData, load_im_info};
+use m17n_mim_rs::lisp_parser::parse_mim;
+use std::sync::Arc;
+
+#[test]
+fn test_include_mim() {
+    let im_info = include_mim!("../tests/fixtures/bn-sample.mim");
+
+    assert_eq!(im_info.name, "sample");
+    assert_eq!(im_info.title.text, "Bengali Sample");
+    assert!(
+        im_info
+            .maps
+            .values()
+            .all(|map| matches!(map.fst.as_fst().as_inner(), FstData::Static(_)))
+    );
+
+    let loaded =
+        load_im_info(parse_mim(include_str!("../../tests/fixtures/bn-sample.mim")).unwrap());
+    let input = "kakhi gim. okar nr";
+    assert_eq!(
+        M17nMim::from_im_info(Arc::clone(im_info)).convert(input),
+        M17nMim::from_im_info(loaded).convert(input)
+    );
+}
+
+#[test]
+fn test_decoded_once() {
+    let embedded = || include_mim!("../tests/fixtures/bn-sample.mim");
+    assert!(Arc::ptr_eq(embedded(), embedded()));
+}
@@ -0,0 +1,178 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Conversion with a map from input positions to output positions.
+
+use crate::context::{Context, Step};
+use crate::im_info::ImInfo;
+use crate::json;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+/// A rule of a map, by map name and its index in definition order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleRef {
+    pub map_name: String,
+    pub rule: usize,
+}
+
+/// The output produced by one step of a conversion.
+///
+/// Ranges count characters, or UTF-16 code units after
+/// [`Conversion::in_utf16`]. A step that produced nothing, or whose output
+/// was later rewritten by another step, has an empty output range at the
+/// position where its output would be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// The keys consumed by the step; none if it pushed back all its keys
+    /// or ran a `nil` branch that shifted to another state
+    pub input: Range<usize>,
+    pub output: Range<usize>,
+    /// The rule that matched, or `None` if no rule matched
+    pub rule: Option<RuleRef>,
+}
+
+/// The result of [`convert_aligned`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversion {
+    pub output: String,
+    /// One segment per step, in input order. Input ranges cover the input
+    /// and output ranges cover the output, each without gaps or overlaps.
+    pub segments: Vec<Segment>,
+}
+
+impl Conversion {
+    /// The output range of the segment containing input character `pos`.
+    pub fn output_range_at(&self, pos: usize) -> Option<Range<usize>> {
+        self.segments
+            .iter()
+            .find(|segment| segment.input.contains(&pos))
+            .map(|segment| segment.output.clone())
+    }
+
+    /// The same conversion with ranges counting UTF-16 code units rather
+    /// than characters, as JavaScript indexes strings. `input` is the
+    /// string that was converted.
+    pub fn in_utf16(&self, input: &str) -> Conversion {
+        let input_offsets = utf16_offsets(input);
+        let output_offsets = utf16_offsets(&self.output);
+        let map =
+            |range: &Range<usize>, offsets: &[usize]| offsets[range.start]..offsets[range.end];
+        Conversion {
+            output: self.output.clone(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| Segment {
+                    input: map(&segment.input, &input_offsets),
+                    output: map(&segment.output, &output_offsets),
+                    rule: segment.rule.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The conversion as JSON, with ranges as `[start, end]` pairs in the
+    /// units they are counted in.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"output\":");
+        json::push_str(&mut out, &self.output);
+        out.push_str(",\"segments\":[");
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"input\":[{},{}],\"output\":[{},{}],\"map\":",
+                segment.input.start, segment.input.end, segment.output.start, segment.output.end
+            );
+            json::push_opt_str(&mut out, segment.rule.as_ref().map(|r| r.map_name.as_str()));
+            match &segment.rule {
+                Some(rule) => {
+                    let _ = write!(out, ",\"rule\":{}}}", rule.rule);
+                }
+                None => out.push_str(",\"rule\":null}"),
+            }
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Convert `input` as [`convert`](crate::context::convert) does, recording
+/// which output each step produced.
+///
+/// A step owns the text it adds or rewrites at the end of the text so far;
+/// when a step deletes or changes text produced by earlier steps, that text
+/// moves to it. So output ranges always follow input order.
+pub fn convert_aligned(im_info: &ImInfo, input: &str) -> Conversion {
+    let mut ctx = Context::new(im_info.initial_state());
+    let keys: Vec<char> = input.chars().collect();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut committed_chars = 0;
+    let mut i = 0;
+
+    while i < keys.len() {
+        let preedit_before = ctx.preedit.clone();
+        let committed_before = ctx.committed.len();
+
+        let step = ctx.step_or_commit(im_info, &keys[i..]);
+        let len = step.consumed();
+        let rule = match step {
+            Step::Matched { found, .. } => Some(RuleRef {
+                map_name: found.map_name.into(),
+                rule: found.rule,
+            }),
+            Step::Nil { .. } | Step::Unmatched => None,
+        };
+
+        // Committed text only grows, so only the text after what was
+        // committed before this step can have changed
+        let newly_committed = &ctx.committed[committed_before..];
+        let unchanged = preedit_before
+            .iter()
+            .zip(newly_committed.chars().chain(ctx.preedit.iter().copied()))
+            .take_while(|(before, after)| *before == after)
+            .count();
+        let start = committed_chars + unchanged;
+        let end = committed_chars + newly_committed.chars().count() + ctx.preedit.len();
+
+        for segment in segments.iter_mut().rev() {
+            if segment.output.end <= start {
+                break;
+            }
+            segment.output.start = segment.output.start.min(start);
+            segment.output.end = start;
+        }
+        segments.push(Segment {
+            input: i..i + len,
+            output: start..end,
+            rule,
+        });
+
+        committed_chars += newly_committed.chars().count();
+        i += len;
+    }
+
+    ctx.commit();
+    Conversion {
+        output: ctx.committed,
+        segments,
+    }
+}
+
+/// The UTF-16 offset of each character of `text`, and of its end.
+fn utf16_offsets(text: &str) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut offset = 0;
+    offsets.push(0);
+    for ch in text.chars() {
+        offset += ch.len_utf16();
+        offsets.push(offset);
+    }
+    offsets
+}
@@ -0,0 +1,297 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Converting large amounts of text on several threads.
+//!
+//! Text is converted in units, lines or paragraphs, each starting from the
+//! input method's initial state. Units are read a chunk at a time, the chunk
+//! is split between worker threads, and the results come out in input order.
+
+use crate::context::convert;
+use crate::im_info::ImInfo;
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How text read by [`convert_reader`] is split into units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Boundary {
+    /// Every line is a unit
+    #[default]
+    Line,
+    /// Every run of non-blank lines is a unit. Line breaks inside a
+    /// paragraph are passed through like any other unmatched character, so
+    /// the input method state carries over from one line to the next.
+    Paragraph,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchOptions {
+    pub boundary: Boundary,
+    /// Number of worker threads, or 0 for one per available CPU.
+    pub threads: usize,
+    /// Number of units read and converted at a time.
+    pub chunk_size: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            boundary: Boundary::Line,
+            threads: 0,
+            chunk_size: 4096,
+        }
+    }
+}
+
+/// Counts and timing of a batch conversion.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BatchStats {
+    pub units: usize,
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl BatchStats {
+    pub fn units_per_second(&self) -> f64 {
+        per_second(self.units, self.elapsed)
+    }
+
+    pub fn input_bytes_per_second(&self) -> f64 {
+        per_second(self.input_bytes, self.elapsed)
+    }
+}
+
+fn per_second(count: usize, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        count as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+/// Convert every item of `units` on its own, yielding the results in order.
+///
+/// Items are taken a chunk at a time as the iterator is advanced, so the
+/// input need not fit in memory. `options.boundary` is not used: each item
+/// is a unit.
+pub fn convert_batch<'a, I>(
+    im_info: &'a ImInfo,
+    units: I,
+    options: &BatchOptions,
+) -> Batch<'a, I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str> + Sync,
+{
+    Batch {
+        im_info,
+        units: units.into_iter(),
+        threads: thread_count(options),
+        chunk_size: options.chunk_size.max(1),
+        converted: Vec::new().into_iter(),
+        stats: BatchStats::default(),
+        started: Instant::now(),
+    }
+}
+
+/// Iterator over converted units, returned by [`convert_batch`].
+pub struct Batch<'a, I> {
+    im_info: &'a ImInfo,
+    units: I,
+    threads: usize,
+    chunk_size: usize,
+    converted: std::vec::IntoIter<String>,
+    stats: BatchStats,
+    started: Instant,
+}
+
+impl<I> Batch<'_, I> {
+    /// Statistics for the units converted so far.
+    pub fn stats(&self) -> BatchStats {
+        BatchStats {
+            elapsed: self.started.elapsed(),
+            ..self.stats
+        }
+    }
+}
+
+impl<I> Iterator for Batch<'_, I>
+where
+    I: Iterator,
+    I::Item: AsRef<str> + Sync,
+{
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if let Some(output) = self.converted.next() {
+            return Some(output);
+        }
+
+        let chunk: Vec<I::Item> = self.units.by_ref().take(self.chunk_size).collect();
+        if chunk.is_empty() {
+            return None;
+        }
+
+        let converted = convert_parallel(self.im_info, &chunk, self.threads);
+        self.stats.units += chunk.len();
+        self.stats.input_bytes += chunk.iter().map(|unit| unit.as_ref().len()).sum::<usize>();
+        self.stats.output_bytes += converted.iter().map(String::len).sum::<usize>();
+        self.converted = converted.into_iter();
+        self.converted.next()
+    }
+}
+
+/// Convert all text from `reader` into `writer`, unit by unit as set by
+/// `options.boundary`. Line breaks and blank lines between units are copied
+/// as they are.
+pub fn convert_reader<R: BufRead, W: Write>(
+    im_info: &ImInfo,
+    mut reader: R,
+    mut writer: W,
+    options: &BatchOptions,
+) -> io::Result<BatchStats> {
+    let started = Instant::now();
+    let threads = thread_count(options);
+    let chunk_size = options.chunk_size.max(1);
+    let mut stats = BatchStats::default();
+    let mut units = UnitReader {
+        boundary: options.boundary,
+        pending: None,
+    };
+
+    loop {
+        let mut texts = Vec::new();
+        let mut separators = Vec::new();
+        while texts.len() < chunk_size {
+            let Some((text, separator)) = units.read(&mut reader)? else {
+                break;
+            };
+            texts.push(text);
+            separators.push(separator);
+        }
+        if texts.is_empty() {
+            break;
+        }
+
+        let converted = convert_parallel(im_info, &texts, threads);
+        for ((text, separator), output) in texts.iter().zip(&separators).zip(&converted) {
+            writer.write_all(output.as_bytes())?;
+            writer.write_all(separator.as_bytes())?;
+            stats.units += 1;
+            stats.input_bytes += text.len() + separator.len();
+            stats.output_bytes += output.len() + separator.len();
+        }
+    }
+
+    writer.flush()?;
+    stats.elapsed = started.elapsed();
+    Ok(stats)
+}
+
+/// Splits text into units, each with the line break and blank lines that
+/// follow it.
+struct UnitReader {
+    boundary: Boundary,
+    /// A line read past the end of the previous paragraph
+    pending: Option<String>,
+}
+
+impl UnitReader {
+    fn read<R: BufRead>(&mut self, reader: &mut R) -> io::Result<Option<(String, String)>> {
+        let mut text = String::new();
+        let mut separator = String::new();
+
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line)? == 0 {
+                        break;
+                    }
+                    line
+                }
+            };
+
+            match self.boundary {
+                Boundary::Line => {
+                    text = line;
+                    break;
+                }
+                Boundary::Paragraph if line.trim().is_empty() => separator.push_str(&line),
+                Boundary::Paragraph if !separator.is_empty() => {
+                    self.pending = Some(line);
+                    break;
+                }
+                Boundary::Paragraph => text.push_str(&line),
+            }
+        }
+
+        if text.is_empty() && separator.is_empty() {
+            return Ok(None);
+        }
+
+        // The line break ending the unit belongs to the separator
+        let break_len = if text.ends_with("\r\n") {
+            2
+        } else if text.ends_with('\n') {
+            1
+        } else {
+            0
+        };
+        separator.insert_str(0, &text[text.len() - break_len..]);
+        text.truncate(text.len() - break_len);
+        Ok(Some((text, separator)))
+    }
+}
+
+fn thread_count(options: &BatchOptions) -> usize {
+    if options.threads > 0 {
+        options.threads
+    } else {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+/// Convert `units` split evenly between up to `threads` threads, keeping
+/// their order.
+fn convert_parallel<T: AsRef<str> + Sync>(
+    im_info: &ImInfo,
+    units: &[T],
+    threads: usize,
+) -> Vec<String> {
+    let threads = threads.clamp(1, units.len().max(1));
+    if threads == 1 {
+        return units
+            .iter()
+            .map(|unit| convert(im_info, unit.as_ref()))
+            .collect();
+    }
+
+    let per_thread = units.len().div_ceil(threads);
+    thread::scope(|scope| {
+        let workers: Vec<_> = units
+            .chunks(per_thread)
+            .map(|part| {
+                scope.spawn(move || {
+                    part.iter()
+                        .map(|unit| convert(im_info, unit.as_ref()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,314 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Portions derived from the M17N library
+//   Copyright (C) 2003–2012 AIST (H15PRO112)
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+use crate::lisp_parser::Element;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Index of a variable slot in [`ImInfo::variables`](crate::im_info::ImInfo).
+pub type VarId = u32;
+
+/// Index of a state name in [`ImInfo::state_names`](crate::im_info::ImInfo).
+pub type StateId = u32;
+
+/// Interned names, numbered in order of first use.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    names: Vec<String>,
+    ids: BTreeMap<String, u32>,
+}
+
+impl Symbols {
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (id as u32, name.as_str()))
+    }
+}
+
+/// A position argument of `delete` and `move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// `@<`
+    Start,
+    /// `@>`
+    End,
+    /// `@-`
+    Prev,
+    /// `@+`
+    Next,
+    /// An integer offset from the current position
+    Relative(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Eq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+/// One instruction of a compiled action list.
+///
+/// Expressions are evaluated on a value stack: `Push*`, `Binary` and `Not`
+/// work on the stack, and `Set` and `JumpIfZero` pop from it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Insert(Box<str>),
+    InsertChar(char),
+    /// Insert the character whose code is in a variable, if it is positive
+    InsertVar(VarId),
+    Delete(Position),
+    Move(Position),
+    Set(VarId),
+    Shift(StateId),
+    Commit,
+    /// Hand the last N keys of the matched KEYSEQ back to be handled again,
+    /// or all of them for 0
+    Pushback(u32),
+    PushInt(i64),
+    PushVar(VarId),
+    /// Push the character at a surrounding text offset, `@-N` or `@+N`
+    PushSurrounding(i32),
+    Binary(BinaryOp),
+    Not,
+    JumpIfZero(u32),
+    Jump(u32),
+    /// An action this implementation does not know; it does nothing
+    Unknown(Box<str>),
+}
+
+/// The compiled form of a rule's map actions or a branch's actions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Program {
+    pub ops: Vec<Op>,
+}
+
+impl Program {
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Indices of the tests of the `cond` arms in this program, in the
+    /// order they appear. Each arm's test is a [`Op::JumpIfZero`] over the
+    /// arm's actions.
+    pub fn cond_arms(&self) -> Vec<usize> {
+        self.ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| matches!(op, Op::JumpIfZero(_)))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+/// Compiles action lists, interning variable and state names as it goes.
+pub struct Compiler<'a> {
+    pub variables: &'a mut Symbols,
+    pub states: &'a mut Symbols,
+}
+
+impl Compiler<'_> {
+    pub fn compile(&mut self, actions: &[Element]) -> Program {
+        let mut ops = Vec::new();
+        for action in actions {
+            self.compile_action(action, &mut ops);
+        }
+        Program { ops }
+    }
+
+    fn compile_action(&mut self, action: &Element, ops: &mut Vec<Op>) {
+        match action {
+            Element::Str(s) => ops.push(Op::Insert(s.as_str().into())),
+            Element::Int(ch) => ops.push(Op::InsertChar(int_to_char(*ch))),
+            Element::Symbol(sym) => ops.push(Op::Unknown(sym.as_str().into())),
+            Element::List(list) => {
+                let Some(Element::Symbol(cmd)) = list.first() else {
+                    return;
+                };
+
+                match (cmd.as_str(), &list[1..]) {
+                    ("insert", [arg, ..]) => match arg {
+                        Element::Str(s) => ops.push(Op::Insert(s.as_str().into())),
+                        Element::Int(ch) => ops.push(Op::InsertChar(int_to_char(*ch))),
+                        Element::Symbol(var) => ops.push(Op::InsertVar(self.variables.intern(var))),
+                        _ => {}
+                    },
+                    ("delete", [arg, ..]) => {
+                        if let Some(pos) = compile_position(arg) {
+                            ops.push(Op::Delete(pos));
+                        }
+                    }
+                    ("move", [arg, ..]) => {
+                        if let Some(pos) = compile_position(arg) {
+                            ops.push(Op::Move(pos));
+                        }
+                    }
+                    ("set", [Element::Symbol(var), expr, ..]) => {
+                        self.compile_expr(expr, ops);
+                        ops.push(Op::Set(self.variables.intern(var)));
+                    }
+                    ("shift", [Element::Symbol(state), ..]) => {
+                        ops.push(Op::Shift(self.states.intern(state)));
+                    }
+                    ("commit", _) => ops.push(Op::Commit),
+                    ("pushback", [Element::Int(keys), ..]) => {
+                        ops.push(Op::Pushback((*keys).clamp(0, u32::MAX as i64) as u32));
+                    }
+                    ("cond", arms) => {
+                        // Each arm tests its condition and jumps to the next
+                        // arm if it is zero; a taken arm jumps to the end
+                        let mut end_jumps = Vec::new();
+                        for arm in arms {
+                            if let Element::List(arm) = arm
+                                && let [cond, actions @ ..] = arm.as_slice()
+                            {
+                                self.compile_expr(cond, ops);
+                                let test = ops.len();
+                                ops.push(Op::JumpIfZero(0));
+                                for action in actions {
+                                    self.compile_action(action, ops);
+                                }
+                                end_jumps.push(ops.len());
+                                ops.push(Op::Jump(0));
+                                ops[test] = Op::JumpIfZero(ops.len() as u32);
+                            }
+                        }
+                        for jump in end_jumps {
+                            ops[jump] = Op::Jump(ops.len() as u32);
+                        }
+                    }
+                    ("insert" | "delete" | "move" | "set" | "shift", _) => {}
+                    (cmd, _) => ops.push(Op::Unknown(cmd.into())),
+                }
+            }
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Element, ops: &mut Vec<Op>) {
+        match expr {
+            Element::Int(i) => ops.push(Op::PushInt(*i)),
+            Element::Symbol(s) => {
+                if s.starts_with("@-") || s.starts_with("@+") {
+                    let offset: i32 = s[1..].parse().unwrap_or(0);
+                    ops.push(Op::PushSurrounding(offset));
+                } else {
+                    ops.push(Op::PushVar(self.variables.intern(s)));
+                }
+            }
+            Element::List(list) => {
+                let Some(Element::Symbol(op)) = list.first() else {
+                    ops.push(Op::PushInt(0));
+                    return;
+                };
+                let args = &list[1..];
+
+                let binary = match op.as_str() {
+                    "+" => BinaryOp::Add,
+                    "-" => BinaryOp::Sub,
+                    "*" => BinaryOp::Mul,
+                    "/" => BinaryOp::Div,
+                    "&" => BinaryOp::And,
+                    "|" => BinaryOp::Or,
+                    "=" | "==" => BinaryOp::Eq,
+                    "<" => BinaryOp::Lt,
+                    ">" => BinaryOp::Gt,
+                    "<=" => BinaryOp::Le,
+                    ">=" => BinaryOp::Ge,
+                    "!" if !args.is_empty() => {
+                        self.compile_expr(&args[0], ops);
+                        ops.push(Op::Not);
+                        return;
+                    }
+                    _ => {
+                        ops.push(Op::PushInt(0));
+                        return;
+                    }
+                };
+
+                if args.len() < 2 {
+                    ops.push(Op::PushInt(0));
+                    return;
+                }
+
+                // Arithmetic and bitwise operators take any number of
+                // arguments, relations compare the first two
+                let operands = match binary {
+                    BinaryOp::Eq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                        &args[..2]
+                    }
+                    _ => args,
+                };
+                self.compile_expr(&operands[0], ops);
+                for operand in &operands[1..] {
+                    self.compile_expr(operand, ops);
+                    ops.push(Op::Binary(binary));
+                }
+            }
+            Element::Str(_) => ops.push(Op::PushInt(0)),
+        }
+    }
+}
+
+fn compile_position(arg: &Element) -> Option<Position> {
+    match arg {
+        Element::Symbol(s) => match s.as_str() {
+            "@<" => Some(Position::Start),
+            "@>" => Some(Position::End),
+            "@-" => Some(Position::Prev),
+            "@+" => Some(Position::Next),
+            _ => None,
+        },
+        Element::Int(0) => Some(Position::Start),
+        Element::Int(1) => Some(Position::Next),
+        Element::Int(-1) => Some(Position::Prev),
+        Element::Int(i) => Some(Position::Relative(
+            (*i).clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+        )),
+        _ => None,
+    }
+}
+
+fn int_to_char(code: i64) -> char {
+    char::from_u32(code as u32).unwrap_or('?')
+}
@@ -0,0 +1,137 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Unicode character names, loaded from the Unicode Character Database.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Character names, loaded from a UnicodeData.txt file.
+///
+/// The names are not built in, since the full table is larger than most
+/// users of this crate want to ship.
+#[derive(Debug, Default, Clone)]
+pub struct CharNames {
+    names: BTreeMap<char, String>,
+    /// Ranges of characters whose names are derived from their code points
+    ranges: Vec<(char, char, DerivedName)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DerivedName {
+    /// A prefix followed by the code point in hex
+    Prefix(&'static str),
+    /// Built from the names of the jamo it is made of
+    HangulSyllable,
+}
+
+impl CharNames {
+    pub fn new() -> Self {
+        CharNames::default()
+    }
+
+    pub fn insert(&mut self, ch: char, name: &str) {
+        self.names.insert(ch, name.to_string());
+    }
+
+    /// The name of `ch`, such as `BENGALI LETTER KA`.
+    pub fn name(&self, ch: char) -> Option<String> {
+        if let Some(name) = self.names.get(&ch) {
+            return Some(name.clone());
+        }
+        let &(_, _, derived) = self
+            .ranges
+            .iter()
+            .find(|(first, last, _)| (*first..=*last).contains(&ch))?;
+        match derived {
+            DerivedName::Prefix(prefix) => Some(format!("{}{:04X}", prefix, ch as u32)),
+            DerivedName::HangulSyllable => hangul_syllable_name(ch),
+        }
+    }
+
+    /// Number of names loaded, not counting the derived names of ranges.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.ranges.is_empty()
+    }
+
+    /// Parse the UnicodeData.txt file of the Unicode Character Database.
+    ///
+    /// Controls, which have no name of their own, are skipped. Of the
+    /// ranges given as `<..., First>` and `<..., Last>` lines, ideographs
+    /// and Hangul syllables get their derived names; the others, such as
+    /// private use, have none.
+    pub fn from_unicode_data(text: &str) -> CharNames {
+        let mut names = CharNames::new();
+        let mut first: Option<char> = None;
+
+        for line in text.lines() {
+            let mut fields = line.split(';');
+            let (Some(code), Some(name)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some(ch) = u32::from_str_radix(code.trim(), 16)
+                .ok()
+                .and_then(char::from_u32)
+            else {
+                continue;
+            };
+
+            let Some(range) = name.strip_prefix('<').and_then(|n| n.strip_suffix('>')) else {
+                names.insert(ch, name);
+                continue;
+            };
+            if range.ends_with(", First") {
+                first = Some(ch);
+            } else if let Some(range) = range.strip_suffix(", Last")
+                && let Some(first) = first.take()
+                && let Some(derived) = derived_name(range)
+            {
+                names.ranges.push((first, ch, derived));
+            }
+        }
+
+        names
+    }
+}
+
+/// How the characters of a range named `range` in UnicodeData.txt are
+/// named, following rules NR1 and NR2 of the Unicode Standard, section 4.8.
+fn derived_name(range: &str) -> Option<DerivedName> {
+    if range.starts_with("CJK Ideograph") {
+        Some(DerivedName::Prefix("CJK UNIFIED IDEOGRAPH-"))
+    } else if range.starts_with("Tangut Ideograph") {
+        Some(DerivedName::Prefix("TANGUT IDEOGRAPH-"))
+    } else if range == "Hangul Syllable" {
+        Some(DerivedName::HangulSyllable)
+    } else {
+        None
+    }
+}
+
+const JAMO_L: [&str; 19] = [
+    "G", "GG", "N", "D", "DD", "R", "M", "B", "BB", "S", "SS", "", "J", "JJ", "C", "K", "T", "P",
+    "H",
+];
+const JAMO_V: [&str; 21] = [
+    "A", "AE", "YA", "YAE", "EO", "E", "YEO", "YE", "O", "WA", "WAE", "OE", "YO", "U", "WEO", "WE",
+    "WI", "YU", "EU", "YI", "I",
+];
+const JAMO_T: [&str; 28] = [
+    "", "G", "GG", "GS", "N", "NJ", "NH", "D", "L", "LG", "LM", "LB", "LS", "LT", "LP", "LH", "M",
+    "B", "BS", "S", "SS", "NG", "J", "C", "K", "T", "P", "H",
+];
+
+fn hangul_syllable_name(ch: char) -> Option<String> {
+    let index = (ch as u32).checked_sub(0xAC00)? as usize;
+    let l = JAMO_L.get(index / (21 * 28))?;
+    let v = JAMO_V[index % (21 * 28) / 28];
+    let t = JAMO_T[index % 28];
+    Some(format!("HANGUL SYLLABLE {}{}{}", l, v, t))
+}
@@ -0,0 +1,359 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! A reference of what to type for each output, for the people using an
+//! input method.
+
+use crate::bytecode::*;
+use crate::char_names::CharNames;
+use crate::im_info::ImInfo;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// One rule of a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetEntry {
+    pub keyseq: String,
+    /// The text the rule inserts, not counting inserts inside a `cond`
+    pub output: String,
+    /// What else the rule does that changes its output, such as a `cond`
+    /// and the text each of its arms inserts
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SheetSection {
+    pub map_name: String,
+    /// The states with a branch for the map, in definition order
+    pub states: Vec<String>,
+    /// Rules in definition order. Rules whose KEYSEQ is redefined later in
+    /// the map never fire and are left out.
+    pub entries: Vec<SheetEntry>,
+}
+
+/// The result of [`cheat_sheet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatSheet {
+    pub title: String,
+    pub lang: String,
+    /// Maps in the order states first use them, then maps no state uses
+    pub sections: Vec<SheetSection>,
+}
+
+/// Build the cheat sheet of `im_info`, with a section for each map.
+pub fn cheat_sheet(im_info: &ImInfo) -> CheatSheet {
+    let mut order: Vec<&str> = Vec::new();
+    for state in &im_info.states {
+        for branch in &state.branches {
+            if im_info.maps.contains_key(&branch.map_name) && !order.contains(&&*branch.map_name) {
+                order.push(&branch.map_name);
+            }
+        }
+    }
+    for name in im_info.maps.keys() {
+        if !order.contains(&name.as_str()) {
+            order.push(name);
+        }
+    }
+
+    let sections = order
+        .into_iter()
+        .map(|map_name| {
+            let map = &im_info.maps[map_name];
+            let mut keyseqs = map.keyseqs();
+            keyseqs.sort_by_key(|(_, rule)| *rule);
+            SheetSection {
+                map_name: map_name.to_string(),
+                states: im_info
+                    .states
+                    .iter()
+                    .filter(|state| state.branches.iter().any(|b| b.map_name == map_name))
+                    .map(|state| state.name.clone())
+                    .collect(),
+                entries: keyseqs
+                    .into_iter()
+                    .filter_map(|(keyseq, rule)| Some(entry(im_info, keyseq, map.rules.get(rule)?)))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let title = match im_info.title.text.as_str() {
+        "" => im_info.name.clone(),
+        title => title.to_string(),
+    };
+    CheatSheet {
+        title,
+        lang: im_info.lang.clone(),
+        sections,
+    }
+}
+
+fn entry(im_info: &ImInfo, keyseq: String, program: &Program) -> SheetEntry {
+    let ops = &program.ops;
+
+    // The ops of each `cond` arm, from after its test to its jump to the
+    // end of the `cond`
+    let arms: Vec<(usize, usize)> = ops
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, op)| match op {
+            Op::JumpIfZero(target) => Some((idx + 1, *target as usize)),
+            _ => None,
+        })
+        .collect();
+    let in_arm = |idx: usize| arms.iter().any(|&(start, end)| (start..end).contains(&idx));
+
+    let output = inserted(
+        ops.iter()
+            .enumerate()
+            .filter(|(idx, _)| !in_arm(*idx))
+            .map(|(_, op)| op),
+    );
+    let mut notes = Vec::new();
+
+    // Arms inside other arms are part of the outer arm's output
+    let outer: Vec<&(usize, usize)> = arms
+        .iter()
+        .filter(|&&(start, _)| !arms.iter().any(|&(s, e)| s < start && start < e))
+        .collect();
+    if !outer.is_empty() {
+        let choices: Vec<String> = outer
+            .iter()
+            .map(|&&(start, end)| format!("\"{}\"", inserted(&ops[start..end.min(ops.len())])))
+            .collect();
+        notes.push(format!("cond: inserts one of {}", choices.join(", ")));
+    }
+
+    for op in ops {
+        let note = match op {
+            Op::InsertVar(var) => format!(
+                "inserts the character in variable {}",
+                im_info.variables.name(*var).unwrap_or_default()
+            ),
+            Op::Delete(_) => "deletes text".to_string(),
+            Op::Move(_) => "moves the cursor".to_string(),
+            Op::Pushback(_) => "hands keys back to be typed again".to_string(),
+            _ => continue,
+        };
+        if !notes.contains(&note) {
+            notes.push(note);
+        }
+    }
+
+    SheetEntry {
+        keyseq,
+        output,
+        notes,
+    }
+}
+
+fn inserted<'a>(ops: impl IntoIterator<Item = &'a Op>) -> String {
+    let mut text = String::new();
+    for op in ops {
+        match op {
+            Op::Insert(s) => text.push_str(s),
+            Op::InsertChar(ch) => text.push(*ch),
+            _ => {}
+        }
+    }
+    text
+}
+
+impl CheatSheet {
+    /// The cheat sheet as Markdown: a heading and a table per map. The
+    /// names column is left out if `names` is empty, and the notes column
+    /// if no rule of the map has notes.
+    pub fn to_markdown(&self, names: &CharNames) -> String {
+        let mut out = format!("# {}\n", self.title);
+        for section in &self.sections {
+            let _ = write!(out, "\n## {}\n\n", section.map_name);
+            if section.states.is_empty() {
+                out.push_str("Not used by any state.\n\n");
+            } else {
+                let _ = write!(out, "States: {}\n\n", section.states.join(", "));
+            }
+
+            let with_names = !names.is_empty();
+            let with_notes = section.entries.iter().any(|entry| !entry.notes.is_empty());
+            out.push_str("| Keys | Output | Code points |");
+            if with_names {
+                out.push_str(" Names |");
+            }
+            if with_notes {
+                out.push_str(" Notes |");
+            }
+            out.push_str("\n|---|---|---|");
+            if with_names {
+                out.push_str("---|");
+            }
+            if with_notes {
+                out.push_str("---|");
+            }
+            out.push('\n');
+
+            for entry in &section.entries {
+                let _ = write!(
+                    out,
+                    "| {} | {} | {} |",
+                    markdown_code(&entry.keyseq),
+                    markdown_cell(&entry.output),
+                    code_points(&entry.output)
+                );
+                if with_names {
+                    let _ = write!(
+                        out,
+                        " {} |",
+                        markdown_cell(&char_names(names, &entry.output))
+                    );
+                }
+                if with_notes {
+                    let _ = write!(out, " {} |", markdown_cell(&entry.notes.join("; ")));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// The cheat sheet as a standalone HTML page, laid out in columns to
+    /// fit on few printed pages. Columns are left out as in
+    /// [`CheatSheet::to_markdown`].
+    pub fn to_html(&self, names: &CharNames) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html");
+        if !self.lang.is_empty() && self.lang != "t" {
+            let _ = write!(out, " lang=\"{}\"", html_escape(&self.lang));
+        }
+        let _ = write!(
+            out,
+            ">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n",
+            html_escape(&self.title)
+        );
+        out.push_str(concat!(
+            "<style>\n",
+            "body { font: 10pt sans-serif; margin: 1em; columns: 20em; column-gap: 2em; }\n",
+            "h1 { column-span: all; font-size: 14pt; }\n",
+            "section { break-inside: avoid; margin-bottom: 1em; }\n",
+            "h2 { font-size: 11pt; margin: 0 0 0.2em; }\n",
+            "p { margin: 0 0 0.3em; font-size: 8pt; color: #555; }\n",
+            "table { border-collapse: collapse; width: 100%; }\n",
+            "th, td { border-bottom: 1px solid #ccc; padding: 1px 4px; text-align: left; ",
+            "vertical-align: top; }\n",
+            "th, .cp, .name, .note { font-size: 8pt; }\n",
+            ".cp, .name { color: #555; }\n",
+            "@media print { body { margin: 0; } }\n",
+            "</style>\n",
+            "</head>\n",
+            "<body>\n",
+        ));
+        let _ = writeln!(out, "<h1>{}</h1>", html_escape(&self.title));
+
+        for section in &self.sections {
+            let _ = writeln!(
+                out,
+                "<section>\n<h2>{}</h2>",
+                html_escape(&section.map_name)
+            );
+            if section.states.is_empty() {
+                out.push_str("<p>Not used by any state.</p>\n");
+            } else {
+                let _ = writeln!(
+                    out,
+                    "<p>States: {}</p>",
+                    html_escape(&section.states.join(", "))
+                );
+            }
+
+            let with_names = !names.is_empty();
+            let with_notes = section.entries.iter().any(|entry| !entry.notes.is_empty());
+            out.push_str("<table>\n<tr><th>Keys</th><th>Output</th><th>Code points</th>");
+            if with_names {
+                out.push_str("<th>Names</th>");
+            }
+            if with_notes {
+                out.push_str("<th>Notes</th>");
+            }
+            out.push_str("</tr>\n");
+
+            for entry in &section.entries {
+                let _ = write!(
+                    out,
+                    "<tr><td><kbd>{}</kbd></td><td>{}</td><td class=\"cp\">{}</td>",
+                    html_escape(&entry.keyseq),
+                    html_escape(&entry.output),
+                    code_points(&entry.output)
+                );
+                if with_names {
+                    let _ = write!(
+                        out,
+                        "<td class=\"name\">{}</td>",
+                        html_escape(&char_names(names, &entry.output))
+                    );
+                }
+                if with_notes {
+                    let _ = write!(
+                        out,
+                        "<td class=\"note\">{}</td>",
+                        html_escape(&entry.notes.join("; "))
+                    );
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// The code points of `text`, as `U+0995 U+09BF`.
+pub(crate) fn code_points(text: &str) -> String {
+    let points: Vec<String> = text
+        .chars()
+        .map(|ch| format!("U+{:04X}", ch as u32))
+        .collect();
+    points.join(" ")
+}
+
+/// The names of the characters of `text`, with `?` for those without one.
+fn char_names(names: &CharNames, text: &str) -> String {
+    let names: Vec<String> = text
+        .chars()
+        .map(|ch| names.name(ch).unwrap_or_else(|| "?".to_string()))
+        .collect();
+    names.join(", ")
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', " ")
+}
+
+/// `text` as a Markdown code span, which shows backslashes and most other
+/// characters as they are.
+fn markdown_code(text: &str) -> String {
+    let text = text.replace('|', "\\|").replace('\n', " ");
+    if text.contains('`') {
+        format!("`` {} ``", text)
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
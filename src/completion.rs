@@ -0,0 +1,94 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! What can be typed next after keys that start a longer KEYSEQ.
+
+use crate::bytecode::StateId;
+use crate::context::{Context, Match};
+use crate::im_info::ImInfo;
+use crate::json;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// A KEYSEQ that the pending keys start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    /// The whole KEYSEQ
+    pub keyseq: String,
+    /// The keys still to type, empty if the pending keys are the KEYSEQ
+    pub rest: String,
+    /// The text the rule gives, run from an empty preedit
+    pub output: String,
+    pub map_name: String,
+    /// Index of the rule in its map
+    pub rule: usize,
+}
+
+/// Every KEYSEQ of the maps of state `state` that starts with `pending`,
+/// with the text it gives, shortest first. Of KEYSEQs in several maps, only
+/// the one of the earliest branch is listed, as only that one fires.
+pub fn completions(im_info: &ImInfo, state: StateId, pending: &[char]) -> Vec<Completion> {
+    let Some(state_info) = im_info.state_by_id(state) else {
+        return Vec::new();
+    };
+    let prefix: String = pending.iter().collect();
+
+    let mut found: Vec<Completion> = Vec::new();
+    for (idx, branch) in state_info.branches.iter().enumerate() {
+        let Some(map) = im_info.maps.get(&branch.map_name) else {
+            continue;
+        };
+        for (keyseq, rule) in map.keyseqs_with_prefix(&prefix) {
+            if found.iter().any(|completion| completion.keyseq == keyseq) {
+                continue;
+            }
+
+            let mut ctx = Context::new(state);
+            let keys: Vec<char> = keyseq.chars().collect();
+            ctx.apply_match(
+                im_info,
+                &Match {
+                    len: keys.len(),
+                    branch: idx,
+                    map_name: &branch.map_name,
+                    rule,
+                },
+            );
+            ctx.commit();
+
+            found.push(Completion {
+                rest: keys[pending.len()..].iter().collect(),
+                keyseq,
+                output: ctx.committed,
+                map_name: branch.map_name.clone(),
+                rule,
+            });
+        }
+    }
+
+    found.sort_by_key(|completion| completion.rest.chars().count());
+    found
+}
+
+/// `completions` as a JSON array of objects.
+pub fn completions_to_json(completions: &[Completion]) -> String {
+    let mut out = String::from("[");
+    for (i, completion) in completions.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"keyseq\":");
+        json::push_str(&mut out, &completion.keyseq);
+        out.push_str(",\"rest\":");
+        json::push_str(&mut out, &completion.rest);
+        out.push_str(",\"output\":");
+        json::push_str(&mut out, &completion.output);
+        out.push_str(",\"map\":");
+        json::push_str(&mut out, &completion.map_name);
+        let _ = write!(out, ",\"rule\":{}}}", completion.rule);
+    }
+    out.push(']');
+    out
+}
@@ -0,0 +1,361 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Where longest-match conversion hides rules from the person typing.
+//!
+//! KEYSEQs are matched longest first, so where a state has both "k" and
+//! "kh", typing "k" and then "h" never gives the result of "k" followed by
+//! whatever "h" types after it. [`Session`](crate::session::Session) waits
+//! for the longest KEYSEQ just as [`convert`](crate::context::convert) does;
+//! an input method front end that fires a rule as soon as its KEYSEQ is
+//! typed does not, and gives the "prefix first" result instead.
+//!
+//! States are explored by typing KEYSEQs, and a key taking the `nil`
+//! branch, from the initial state, so a state only reachable through a
+//! `cond` on earlier text may be missed. Rules only such states use are
+//! reported as [`DeadReason::NotReached`], not as dead.
+
+use crate::bytecode::StateId;
+use crate::context::{Context, Match};
+use crate::im_info::ImInfo;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A KEYSEQ that another KEYSEQ of the same state starts with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixConflict {
+    pub state: String,
+    /// The longer KEYSEQ, which wins
+    pub keys: String,
+    /// The shorter KEYSEQ it starts with
+    pub prefix: String,
+    /// An input from the initial state that ends with `keys` typed in
+    /// `state`
+    pub example: String,
+    /// What the example converts to
+    pub batch: String,
+    /// What the example converts to if the rule for `prefix` fires before
+    /// the rest of `keys` is typed
+    pub prefix_first: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadReason {
+    /// No state uses the rule's map
+    MapNotUsed,
+    /// State `state` uses the rule's map, but was not reached by the
+    /// analysis, so whether the rule fires is not known
+    NotReached { state: String },
+    /// A later rule of the map has the same KEYSEQ
+    Redefined,
+    /// A key code of the rule's KEYSEQ is not a character
+    InvalidKeyseq,
+    /// In every state reached that uses the map, an earlier branch matches
+    /// the same KEYSEQ. `example` is typed in the first such state and
+    /// fires rule `rule` of map `map_name` instead.
+    Shadowed {
+        example: String,
+        map_name: String,
+        rule: usize,
+    },
+}
+
+/// A rule that can never fire, or that the analysis could not check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadRule {
+    pub map_name: String,
+    pub rule: usize,
+    /// `None` for a redefined rule, whose KEYSEQ is not kept
+    pub keyseq: Option<String>,
+    pub reason: DeadReason,
+}
+
+/// The result of [`find_conflicts`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConflictReport {
+    pub conflicts: Vec<PrefixConflict>,
+    pub dead_rules: Vec<DeadRule>,
+}
+
+/// A state reached from the initial state, with the keys typed to get there
+/// and the context after them.
+struct Reached {
+    path: Vec<char>,
+    ctx: Context,
+}
+
+/// Find prefix conflicts and rules that can never fire in `im_info`.
+pub fn find_conflicts(im_info: &ImInfo) -> ConflictReport {
+    let mut reached = explore(im_info);
+    let mut report = ConflictReport::default();
+
+    // States in definition order
+    let reached: Vec<(&str, Reached)> = im_info
+        .states
+        .iter()
+        .filter_map(|state| Some((state.name.as_str(), reached.remove(&state.id)?)))
+        .collect();
+
+    for (name, state) in &reached {
+        for keys in state_keyseqs(im_info, &state.ctx) {
+            for split in 1..keys.len() {
+                let (prefix, rest) = keys.split_at(split);
+                if !matches_exactly(im_info, &state.ctx, prefix) {
+                    continue;
+                }
+
+                // An input that loops gives no output to compare
+                let mut batch = state.ctx.clone();
+                let mut prefix_first = state.ctx.clone();
+                if batch.process_keys(im_info, &keys).is_err()
+                    || prefix_first.process_keys(im_info, prefix).is_err()
+                    || prefix_first.process_keys(im_info, rest).is_err()
+                {
+                    continue;
+                }
+
+                let batch = finish(batch);
+                let prefix_first = finish(prefix_first);
+                if batch != prefix_first {
+                    report.conflicts.push(PrefixConflict {
+                        state: name.to_string(),
+                        keys: keys.iter().collect(),
+                        prefix: prefix.iter().collect(),
+                        example: state.path.iter().chain(&keys).collect(),
+                        batch,
+                        prefix_first,
+                    });
+                }
+            }
+        }
+    }
+
+    for (map_name, map) in &im_info.maps {
+        let keyseqs: BTreeMap<usize, String> = map
+            .keyseqs()
+            .into_iter()
+            .map(|(keyseq, rule)| (rule, keyseq))
+            .collect();
+        let uses_map = |id: StateId| {
+            im_info
+                .state_by_id(id)
+                .is_some_and(|s| s.branches.iter().any(|b| &b.map_name == map_name))
+        };
+        let users: Vec<&Reached> = reached
+            .iter()
+            .map(|(_, state)| state)
+            .filter(|state| uses_map(state.ctx.current_state))
+            .collect();
+        let unreached = im_info.states.iter().find(|state| {
+            uses_map(state.id) && !reached.iter().any(|(name, _)| *name == state.name)
+        });
+
+        for rule in 0..map.rules.len() {
+            let keyseq = keyseqs.get(&rule);
+            let reason = match keyseq {
+                None if map.invalid_keyseqs.contains(&(rule as u32)) => {
+                    Some(DeadReason::InvalidKeyseq)
+                }
+                None => Some(DeadReason::Redefined),
+                Some(keyseq) => {
+                    let reason = if users.is_empty() {
+                        Some(DeadReason::MapNotUsed)
+                    } else {
+                        shadowed(im_info, &users, map_name, rule, keyseq)
+                    };
+                    // A state not reached may still fire the rule
+                    match unreached {
+                        Some(state) => reason.map(|_| DeadReason::NotReached {
+                            state: state.name.clone(),
+                        }),
+                        None => reason,
+                    }
+                }
+            };
+            if let Some(reason) = reason {
+                report.dead_rules.push(DeadRule {
+                    map_name: map_name.clone(),
+                    rule,
+                    keyseq: keyseq.cloned(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    report
+}
+
+/// Reach every state that typing KEYSEQs, or a key taking the `nil`
+/// branch, leads to, by the shortest input. Each state's context is that of
+/// converting its whole input at once, so that examples built on it are
+/// sound.
+fn explore(im_info: &ImInfo) -> BTreeMap<StateId, Reached> {
+    let initial = Context::new(im_info.initial_state());
+    let mut reached = BTreeMap::new();
+    let mut pending = VecDeque::from([initial.current_state]);
+    reached.insert(
+        initial.current_state,
+        Reached {
+            path: Vec::new(),
+            ctx: initial,
+        },
+    );
+
+    while let Some(id) = pending.pop_front() {
+        let state = &reached[&id];
+        let mut inputs = state_keyseqs(im_info, &state.ctx);
+        inputs.extend(nil_key(im_info, &state.ctx).map(|key| vec![key]));
+
+        let mut found = Vec::new();
+        for keys in inputs {
+            let path: Vec<char> = state.path.iter().chain(&keys).copied().collect();
+            let mut ctx = Context::new(im_info.initial_state());
+            if ctx.process_keys(im_info, &path).is_err() {
+                continue;
+            }
+            let next = ctx.current_state;
+            if reached.contains_key(&next)
+                || found.iter().any(|(id, _)| *id == next)
+                || im_info.state_by_id(next).is_none()
+            {
+                continue;
+            }
+            found.push((next, Reached { path, ctx }));
+        }
+
+        for (next, state) in found {
+            reached.insert(next, state);
+            pending.push_back(next);
+        }
+    }
+
+    reached
+}
+
+/// A key that starts no KEYSEQ of the maps of `ctx`'s state, to take its
+/// `nil` branch with, if it has one.
+fn nil_key(im_info: &ImInfo, ctx: &Context) -> Option<char> {
+    let state = im_info.state_by_id(ctx.current_state)?;
+    if !state.branches.iter().any(|branch| branch.map_name == "nil") {
+        return None;
+    }
+    let firsts: BTreeSet<char> = state_keyseqs(im_info, ctx)
+        .iter()
+        .filter_map(|keys| keys.first().copied())
+        .collect();
+    core::iter::once(' ')
+        .chain('!'..='~')
+        .find(|key| !firsts.contains(key))
+}
+
+/// The KEYSEQs of the maps of `ctx`'s state.
+fn state_keyseqs(im_info: &ImInfo, ctx: &Context) -> Vec<Vec<char>> {
+    let Some(state) = im_info.state_by_id(ctx.current_state) else {
+        return Vec::new();
+    };
+
+    let names: BTreeSet<&str> = state.branches.iter().map(|b| b.map_name.as_str()).collect();
+    let keyseqs: BTreeSet<String> = names
+        .into_iter()
+        .filter_map(|name| im_info.maps.get(name))
+        .flat_map(|map| map.keyseqs())
+        .map(|(keyseq, _)| keyseq)
+        .collect();
+    keyseqs
+        .into_iter()
+        .map(|keyseq| keyseq.chars().collect())
+        .collect()
+}
+
+fn matches_exactly(im_info: &ImInfo, ctx: &Context, keys: &[char]) -> bool {
+    ctx.find_match(im_info, keys)
+        .is_some_and(|found| found.len == keys.len())
+}
+
+/// Whether `keyseq` of rule `rule` of `map_name` loses to an earlier branch
+/// in every state of `users`.
+fn shadowed(
+    im_info: &ImInfo,
+    users: &[&Reached],
+    map_name: &str,
+    rule: usize,
+    keyseq: &str,
+) -> Option<DeadReason> {
+    let keys: Vec<char> = keyseq.chars().collect();
+    let mut first: Option<(&Reached, Match)> = None;
+
+    for state in users {
+        let found = state.ctx.find_match(im_info, &keys)?;
+        if found.map_name == map_name && found.rule == rule {
+            return None;
+        }
+        first.get_or_insert((state, found));
+    }
+
+    let (state, found) = first?;
+    Some(DeadReason::Shadowed {
+        example: state.path.iter().chain(&keys).collect(),
+        map_name: found.map_name.to_string(),
+        rule: found.rule,
+    })
+}
+
+fn finish(mut ctx: Context) -> String {
+    ctx.commit();
+    ctx.committed
+}
+
+/// The report as text, one line per conflict and per dead rule.
+impl fmt::Display for ConflictReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for conflict in &self.conflicts {
+            writeln!(
+                f,
+                "state {}: \"{}\" hides \"{}\" followed by \"{}\": \"{}\" gives \"{}\", not \"{}\"",
+                conflict.state,
+                conflict.keys,
+                conflict.prefix,
+                &conflict.keys[conflict.prefix.len()..],
+                conflict.example,
+                conflict.batch,
+                conflict.prefix_first
+            )?;
+        }
+        for dead in &self.dead_rules {
+            write!(f, "map {}, rule {}", dead.map_name, dead.rule)?;
+            if let Some(keyseq) = &dead.keyseq {
+                write!(f, " \"{}\"", keyseq)?;
+            }
+            match &dead.reason {
+                DeadReason::MapNotUsed => writeln!(f, " never fires: no state uses the map")?,
+                DeadReason::NotReached { state } => writeln!(
+                    f,
+                    " not reached by the analysis: state {} uses the map",
+                    state
+                )?,
+                DeadReason::Redefined => {
+                    writeln!(f, " never fires: a later rule has the same KEYSEQ")?
+                }
+                DeadReason::InvalidKeyseq => writeln!(
+                    f,
+                    " never fires: a key code of its KEYSEQ is not a character"
+                )?,
+                DeadReason::Shadowed {
+                    example,
+                    map_name,
+                    rule,
+                } => writeln!(
+                    f,
+                    " never fires: \"{}\" fires map {}, rule {} instead",
+                    example, map_name, rule
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,145 @@
-use std::collections::HashMap;
+use crate::bytecode::*;
 use crate::im_info::*;
-use crate::lisp_parser::*;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
 
+/// Number of steps in a row that may consume no key before
+/// [`Context::step`] gives up, unless [`Context::step_budget`] is changed.
+pub const DEFAULT_STEP_BUDGET: usize = 1000;
+
+/// A KEYSEQ matched by [`Context::find_match`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// Number of keys matched
+    pub len: usize,
+    /// Index of the branch in the current state
+    pub branch: usize,
+    pub map_name: &'a str,
+    /// Index of the rule in its map, in definition order
+    pub rule: usize,
+}
+
+/// What one call of [`Context::step`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step<'a> {
+    /// A KEYSEQ matched. `consumed` is less than its length if keys were
+    /// pushed back to be handled again.
+    Matched { found: Match<'a>, consumed: usize },
+    /// Nothing matched and the state's `nil` branch ran. If it shifted to
+    /// another state, the key is handled again there and `consumed` is 0;
+    /// otherwise the key was committed as it is.
+    Nil { branch: usize, consumed: usize },
+    /// Nothing matched; the preedit and the key were committed as they are
+    Unmatched,
+}
+
+impl Step<'_> {
+    /// Number of keys used up by the step.
+    pub fn consumed(&self) -> usize {
+        match self {
+            Step::Matched { consumed, .. } | Step::Nil { consumed, .. } => *consumed,
+            Step::Unmatched => 1,
+        }
+    }
+}
+
+/// The input method went round [`Context::step_budget`] steps without
+/// consuming a key, through `shift`s of `nil` branches or `pushback`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopError {
+    pub steps: usize,
+    /// The key being handled
+    pub key: char,
+    /// The state the input method was in when it gave up
+    pub state: String,
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input method looped for {} steps without consuming key {:?} (in state {})",
+            self.steps, self.key, self.state
+        )
+    }
+}
+
+impl core::error::Error for LoopError {}
+
+/// Which actions an action belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionSource {
+    /// The map actions of the matched rule
+    Rule,
+    /// The actions of the state branch the rule was found through, or of
+    /// the `nil` branch
+    Branch,
+}
+
+impl ActionSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActionSource::Rule => "rule",
+            ActionSource::Branch => "branch",
+        }
+    }
+}
+
+/// An action run while [`Context::trace`] is on, with the preedit and
+/// cursor position around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracedAction {
+    pub source: ActionSource,
+    pub op: Op,
+    pub preedit_before: String,
+    pub cursor_before: usize,
+    pub preedit_after: String,
+    pub cursor_after: usize,
+    /// The value assigned by a `set` action
+    pub value: Option<i64>,
+}
+
+#[derive(Clone)]
 pub struct Context {
     pub preedit: Vec<char>,
     pub current_pos: usize,
     pub committed: String,
-    pub variables: HashMap<String, i64>,
-    pub current_state: String,
+    /// Variable values, indexed by [`VarId`]
+    pub variables: Vec<i64>,
+    pub current_state: StateId,
+    /// If set, every action run is recorded here. Stack operations and
+    /// jumps are not actions and are not recorded.
+    pub trace: Option<Vec<TracedAction>>,
+    /// If set, the index in its program of the test of every `cond` arm
+    /// taken is recorded here, with the actions the program is.
+    pub arms_taken: Option<Vec<(ActionSource, usize)>>,
+    /// Steps in a row that may consume no key before [`Context::step`]
+    /// fails with a [`LoopError`]
+    pub step_budget: usize,
+    idle_steps: usize,
+    /// Keys pushed back by the actions run so far in this step, 0 for all
+    pushback: Option<usize>,
+    /// The actions being run
+    source: ActionSource,
+    stack: Vec<i64>,
 }
 
 impl Context {
-    pub fn new(initial_state: String) -> Self {
+    pub fn new(initial_state: StateId) -> Self {
         Context {
             preedit: Vec::new(),
             current_pos: 0,
             committed: String::new(),
-            variables: HashMap::new(),
+            variables: Vec::new(),
             current_state: initial_state,
+            trace: None,
+            arms_taken: None,
+            step_budget: DEFAULT_STEP_BUDGET,
+            idle_steps: 0,
+            pushback: None,
+            source: ActionSource::Branch,
+            stack: Vec::new(),
         }
     }
 
@@ -33,7 +155,7 @@ impl Context {
         self.current_pos += 1;
     }
 
-    pub fn delete(&mut self, pos: isize) {
+    pub fn delete(&mut self, pos: Position) {
         let target_pos = self.resolve_position(pos);
         if target_pos < self.current_pos {
             self.preedit.drain(target_pos..self.current_pos);
@@ -43,28 +165,32 @@ impl Context {
         }
     }
 
-    pub fn move_pos(&mut self, pos: isize) {
+    pub fn move_pos(&mut self, pos: Position) {
         self.current_pos = self.resolve_position(pos);
     }
 
-    pub fn resolve_position(&self, pos: isize) -> usize {
+    pub fn resolve_position(&self, pos: Position) -> usize {
         match pos {
-            0 => 0,                                              // @<
-            -1 => self.current_pos.saturating_sub(1),            // @-
-            1 => (self.current_pos + 1).min(self.preedit.len()), // @+
-            isize::MAX => self.preedit.len(),                    // @>
-            _ if pos < 0 => self.current_pos.saturating_sub((-pos) as usize),
-            _ => (self.current_pos + pos as usize).min(self.preedit.len()),
+            Position::Start => 0,
+            Position::Prev => self.current_pos.saturating_sub(1),
+            Position::Next => (self.current_pos + 1).min(self.preedit.len()),
+            Position::End => self.preedit.len(),
+            Position::Relative(offset) if offset < 0 => self
+                .current_pos
+                .saturating_sub(offset.unsigned_abs() as usize),
+            Position::Relative(offset) => {
+                (self.current_pos + offset as usize).min(self.preedit.len())
+            }
         }
     }
 
-    pub fn get_char_at(&self, pos: isize) -> i64 {
+    pub fn get_char_at(&self, pos: i32) -> i64 {
         if pos == 0 {
             return -1; // Surrounding text supported
         }
 
         let idx = if pos < 0 {
-            let offset = (-pos) as usize;
+            let offset = pos.unsigned_abs() as usize;
             if offset <= self.current_pos {
                 self.current_pos - offset
             } else {
@@ -89,255 +215,299 @@ impl Context {
         self.current_pos = 0;
     }
 
-    pub fn set_var(&mut self, name: &str, value: i64) {
-        self.variables.insert(name.to_string(), value);
+    pub fn set_var(&mut self, var: VarId, value: i64) {
+        let idx = var as usize;
+        if idx >= self.variables.len() {
+            self.variables.resize(idx + 1, 0);
+        }
+        self.variables[idx] = value;
     }
 
-    pub fn get_var(&self, name: &str) -> i64 {
-        *self.variables.get(name).unwrap_or(&0)
+    pub fn get_var(&self, var: VarId) -> i64 {
+        self.variables.get(var as usize).copied().unwrap_or(0)
     }
 
-    pub fn process_key(&mut self, im_info: &ImInfo, remaining: &[char]) -> usize {
-        // Find current state
-        let state = im_info.states.iter()
-            .find(|s| s.name == self.current_state);
-
-        if state.is_none() {
-            return 0;
+    /// Handle the keys `remaining` starts with: apply the longest KEYSEQ
+    /// among the maps of the current state, or if none matches, run the
+    /// state's `nil` branch or commit the first key as it is.
+    ///
+    /// Fails if the input method has gone [`Context::step_budget`] steps in
+    /// a row without consuming a key. `remaining` must not be empty.
+    pub fn step<'a>(
+        &mut self,
+        im_info: &'a ImInfo,
+        remaining: &[char],
+    ) -> Result<Step<'a>, LoopError> {
+        if self.idle_steps >= self.step_budget {
+            self.idle_steps = 0;
+            return Err(LoopError {
+                steps: self.step_budget,
+                key: remaining.first().copied().unwrap_or_default(),
+                state: im_info.state_name(self.current_state).to_string(),
+            });
         }
 
-        let state = state.unwrap();
-
-        let mut best_match = 0;
-        let mut best_map_actions = None;
-        let mut best_branch_actions = None;
-        // Try to match against each map in the state
-        for branch in &state.branches {
-            if let Some(keyseq_map) = im_info.maps.get(&branch.map_name) {
-                // Try to find longest matching key sequence
-                for len in 1..=remaining.len().min(10) {
-                    let key_str: String = remaining[..len].iter().collect();
-                    let key_bytes = key_str.as_bytes();
-
-                    // Check if this key sequence exists in the FST
-                    if let Some(idx) = keyseq_map.fst.get(key_bytes) && len > best_match {
-                        best_match = len;
-                        best_map_actions = keyseq_map.rules.get(idx as usize);
-                        best_branch_actions = Some(&branch.actions);
-                    }
+        let step = match self.find_match(im_info, remaining) {
+            Some(found) => {
+                self.pushback = None;
+                self.apply_match(im_info, &found);
+                let pushed_back = match self.pushback.take() {
+                    Some(0) => found.len,
+                    Some(keys) => keys.min(found.len),
+                    None => 0,
+                };
+                Step::Matched {
+                    found,
+                    consumed: found.len - pushed_back,
                 }
             }
+            None => self.step_unmatched(im_info, remaining),
+        };
+
+        if step.consumed() == 0 {
+            self.idle_steps += 1;
+        } else {
+            self.idle_steps = 0;
         }
+        Ok(step)
+    }
 
-        // Execute the map actions for the best match
-        if let Some(map_actions) = best_map_actions {
-            if let Element::List(action_list) = map_actions {
-                for action in action_list {
-                    self.execute_action(action);
-                }
-            } else {
-                self.execute_action(map_actions);
+    /// Like [`Context::step`], but if the input method loops, the key is
+    /// committed as it is, as if nothing matched it.
+    pub fn step_or_commit<'a>(&mut self, im_info: &'a ImInfo, remaining: &[char]) -> Step<'a> {
+        self.step(im_info, remaining).unwrap_or_else(|_| {
+            self.commit();
+            self.committed.extend(remaining.first());
+            Step::Unmatched
+        })
+    }
+
+    fn step_unmatched<'a>(&mut self, im_info: &'a ImInfo, remaining: &[char]) -> Step<'a> {
+        let nil = im_info.state_by_id(self.current_state).and_then(|state| {
+            state
+                .branches
+                .iter()
+                .position(|branch| branch.map_name == "nil")
+                .map(|idx| (idx, &state.branches[idx]))
+        });
+
+        let state_before = self.current_state;
+        if let Some((_, branch)) = nil {
+            self.run_from(ActionSource::Branch, &branch.actions);
+            self.pushback = None;
+            if self.current_state != state_before {
+                return Step::Nil {
+                    branch: nil.map_or(0, |(idx, _)| idx),
+                    consumed: 0,
+                };
             }
         }
 
-        // Execute the branch actions for the best match
-        if let Some(branch_actions) = best_branch_actions {
-            for branch_action in branch_actions {
-                self.execute_action(&branch_action);
-            }
+        // No match, commit current preedit and add the key directly
+        self.commit();
+        self.committed.extend(remaining.first());
+        match nil {
+            Some((branch, _)) => Step::Nil {
+                branch,
+                consumed: 1,
+            },
+            None => Step::Unmatched,
         }
+    }
 
-        best_match
+    /// Apply all of `keys` with [`Context::step`].
+    pub fn process_keys(&mut self, im_info: &ImInfo, keys: &[char]) -> Result<(), LoopError> {
+        let mut i = 0;
+        while i < keys.len() {
+            i += self.step(im_info, &keys[i..])?.consumed();
+        }
+        Ok(())
     }
 
-    fn execute_action(&mut self, action: &Element) {
-        match action {
-            Element::Str(s) => self.insert(s),
-            Element::Int(ch) => self.insert_char(char::from_u32(*ch as u32).unwrap_or('?')),
-            Element::List(list) if !list.is_empty() => {
-                if let Element::Symbol(cmd) = &list[0] {
-                    match cmd.as_str() {
-                        "insert" => {
-                            if list.len() > 1 {
-                                match &list[1] {
-                                    Element::Str(s) => self.insert(s),
-                                    Element::Int(ch) => self.insert_char(
-                                        char::from_u32(*ch as u32).unwrap_or('?')
-                                    ),
-                                    Element::Symbol(var) => {
-                                        let val = self.get_var(var);
-                                        if val > 0 {
-                                            self.insert_char(
-                                                char::from_u32(val as u32).unwrap_or('?')
-                                            );
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        "delete" => {
-                            if list.len() > 1 {
-                                if let Some(pos) = self.resolve_pos_arg(&list[1]) {
-                                    self.delete(pos);
-                                }
-                            }
-                        }
-                        "move" => {
-                            if list.len() > 1 {
-                                if let Some(pos) = self.resolve_pos_arg(&list[1]) {
-                                    self.move_pos(pos);
-                                }
-                            }
-                        }
-                        "set" => {
-                            if list.len() > 2 {
-                                if let Element::Symbol(var) = &list[1] {
-                                    let value = self.eval_expr(&list[2]);
-                                    self.set_var(var, value);
-                                }
-                            }
-                        }
-                        "shift" => {
-                            if list.len() > 1 {
-                                if let Element::Symbol(state) = &list[1] {
-                                    self.current_state = state.clone();
-                                }
-                            }
-                        }
-                        "commit" => self.commit(),
-                        "cond" => {
-                            for i in 1..list.len() {
-                                if let Element::List(branch) = &list[i] {
-                                    if !branch.is_empty() {
-                                        let cond_val = self.eval_expr( &branch[0]);
-                                        if cond_val != 0 {
-                                            for j in 1..branch.len() {
-                                                self.execute_action(&branch[j]);
-                                            }
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+    /// Find the longest KEYSEQ that `remaining` starts with among the maps
+    /// of the current state's branches. Of equally long matches, the one in
+    /// the earliest branch wins.
+    pub fn find_match<'a>(&self, im_info: &'a ImInfo, remaining: &[char]) -> Option<Match<'a>> {
+        let state = im_info.state_by_id(self.current_state)?;
+
+        let mut best: Option<Match> = None;
+        for (branch_idx, branch) in state.branches.iter().enumerate() {
+            if let Some(keyseq_map) = im_info.maps.get(&branch.map_name)
+                && let Some((len, rule)) = keyseq_map.longest_match(remaining)
+                && best.is_none_or(|best| len > best.len)
+            {
+                best = Some(Match {
+                    len,
+                    branch: branch_idx,
+                    map_name: &branch.map_name,
+                    rule,
+                });
             }
-            _ => {}
         }
+        best
     }
 
-    fn resolve_pos_arg(&self, arg: &Element) -> Option<isize> {
-        match arg {
-            Element::Symbol(s) => match s.as_str() {
-                "@<" => Some(0),
-                "@>" => Some(isize::MAX),
-                "@-" => Some(-1),
-                "@+" => Some(1),
-                _ => None,
-            },
-            Element::Int(i) => Some(*i as isize),
-            _ => None,
+    /// Run the map actions of a matched rule, then the actions of its
+    /// branch.
+    pub fn apply_match(&mut self, im_info: &ImInfo, found: &Match) {
+        let Some(branch) = im_info
+            .state_by_id(self.current_state)
+            .and_then(|state| state.branches.get(found.branch))
+        else {
+            return;
+        };
+
+        if let Some(map_actions) = im_info
+            .maps
+            .get(found.map_name)
+            .and_then(|map| map.rules.get(found.rule))
+        {
+            self.run_from(ActionSource::Rule, map_actions);
         }
+        self.run_from(ActionSource::Branch, &branch.actions);
+    }
+
+    fn run_from(&mut self, source: ActionSource, program: &Program) {
+        self.source = source;
+        self.run(program);
+        self.source = ActionSource::Branch;
     }
 
-    fn eval_expr(&self, expr: &Element) -> i64 {
-        match expr {
-            Element::Int(i) => *i,
-            Element::Symbol(s) => {
-                if s.starts_with("@-") || s.starts_with("@+") {
-                    let offset: isize = s[1..].parse().unwrap_or(0);
-                    self.get_char_at(offset)
-                } else if s == "@-0" {
-                    -1 // Surrounding text supported
-                } else {
-                    self.get_var(s)
+    /// Execute a compiled action list.
+    pub fn run(&mut self, program: &Program) {
+        let ops = &program.ops;
+        let mut pc = 0;
+
+        while let Some(op) = ops.get(pc) {
+            pc += 1;
+            let before = (self.trace.is_some() && is_action(op))
+                .then(|| (self.preedit.iter().collect::<String>(), self.current_pos));
+
+            match op {
+                Op::Insert(s) => self.insert(s),
+                Op::InsertChar(ch) => self.insert_char(*ch),
+                Op::InsertVar(var) => {
+                    let val = self.get_var(*var);
+                    if val > 0 {
+                        self.insert_char(char::from_u32(val as u32).unwrap_or('?'));
+                    }
                 }
-            }
-            Element::List(list) if !list.is_empty() => {
-                if let Element::Symbol(op) = &list[0] {
-                    match op.as_str() {
-                        "+" if list.len() > 2 => {
-                            self.eval_expr(&list[1]) + self.eval_expr(&list[2])
-                        }
-                        "-" if list.len() > 2 => {
-                            self.eval_expr(&list[1]) - self.eval_expr(&list[2])
-                        }
-                        "*" if list.len() > 2 => {
-                            self.eval_expr(&list[1]) * self.eval_expr(&list[2])
-                        }
-                        "/" if list.len() > 2 => {
-                            let divisor = self.eval_expr(&list[2]);
-                            if divisor != 0 {
-                                self.eval_expr(&list[1]) / divisor
-                            } else {
-                                0
-                            }
-                        }
-                        "&" if list.len() > 2 => {
-                            self.eval_expr(&list[1]) & self.eval_expr(&list[2])
-                        }
-                        "|" if list.len() > 2 => {
-                            self.eval_expr(&list[1]) | self.eval_expr(&list[2])
-                        }
-                        "==" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) == self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        "=" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) == self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        "<" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) < self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        ">" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) > self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        "<=" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) <= self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        ">=" if list.len() > 2 => {
-                            if self.eval_expr(&list[1]) >= self.eval_expr(&list[2]) {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        "!" if list.len() > 1 => {
-                            if self.eval_expr(&list[1]) == 0 {
-                                1
-                            } else {
-                                0
-                            }
-                        }
-                        _ => 0,
+                Op::Delete(pos) => self.delete(*pos),
+                Op::Move(pos) => self.move_pos(*pos),
+                Op::Set(var) => {
+                    let value = self.pop();
+                    self.set_var(*var, value);
+                }
+                Op::Shift(state) => self.current_state = *state,
+                Op::Commit => self.commit(),
+                Op::Pushback(keys) => self.pushback = Some(*keys as usize),
+                Op::PushInt(i) => self.stack.push(*i),
+                Op::PushVar(var) => self.stack.push(self.get_var(*var)),
+                Op::PushSurrounding(offset) => self.stack.push(self.get_char_at(*offset)),
+                Op::Binary(op) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    self.stack.push(eval_binary(*op, lhs, rhs));
+                }
+                Op::Not => {
+                    let value = self.pop();
+                    self.stack.push((value == 0) as i64);
+                }
+                Op::JumpIfZero(target) => {
+                    if self.pop() == 0 {
+                        pc = *target as usize;
+                    } else if let Some(arms) = &mut self.arms_taken {
+                        arms.push((self.source, pc - 1));
                     }
-                } else {
-                    0
                 }
+                Op::Jump(target) => pc = *target as usize,
+                Op::Unknown(_) => {}
+            }
+
+            if let Some((preedit_before, cursor_before)) = before {
+                let action = TracedAction {
+                    source: self.source,
+                    op: op.clone(),
+                    preedit_before,
+                    cursor_before,
+                    preedit_after: self.preedit.iter().collect(),
+                    cursor_after: self.current_pos,
+                    value: match op {
+                        Op::Set(var) => Some(self.get_var(*var)),
+                        _ => None,
+                    },
+                };
+                self.trace.get_or_insert_default().push(action);
+            }
+        }
+
+        self.stack.clear();
+    }
+
+    fn pop(&mut self) -> i64 {
+        self.stack.pop().unwrap_or(0)
+    }
+}
+
+/// Convert a whole input string, as if its characters were typed one after
+/// another and the result committed at the end. A key the input method
+/// loops on is committed as it is.
+pub fn convert(im_info: &ImInfo, input: &str) -> String {
+    let mut ctx = Context::new(im_info.initial_state());
+    let input_chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+
+    while i < input_chars.len() {
+        i += ctx.step_or_commit(im_info, &input_chars[i..]).consumed();
+    }
+
+    // Commit any remaining preedit
+    ctx.commit();
+    ctx.committed
+}
+
+/// Like [`convert`], but fails if the input method loops.
+pub fn try_convert(im_info: &ImInfo, input: &str) -> Result<String, LoopError> {
+    let mut ctx = Context::new(im_info.initial_state());
+    let input_chars: Vec<char> = input.chars().collect();
+    ctx.process_keys(im_info, &input_chars)?;
+    ctx.commit();
+    Ok(ctx.committed)
+}
+
+/// Whether `op` is an action of the .mim file rather than a step in
+/// evaluating an expression or a `cond`.
+fn is_action(op: &Op) -> bool {
+    !matches!(
+        op,
+        Op::PushInt(_)
+            | Op::PushVar(_)
+            | Op::PushSurrounding(_)
+            | Op::Binary(_)
+            | Op::Not
+            | Op::JumpIfZero(_)
+            | Op::Jump(_)
+    )
+}
+
+fn eval_binary(op: BinaryOp, lhs: i64, rhs: i64) -> i64 {
+    match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div => {
+            if rhs != 0 {
+                lhs.wrapping_div(rhs)
+            } else {
+                0
             }
-            _ => 0,
         }
+        BinaryOp::And => lhs & rhs,
+        BinaryOp::Or => lhs | rhs,
+        BinaryOp::Eq => (lhs == rhs) as i64,
+        BinaryOp::Lt => (lhs < rhs) as i64,
+        BinaryOp::Gt => (lhs > rhs) as i64,
+        BinaryOp::Le => (lhs <= rhs) as i64,
+        BinaryOp::Ge => (lhs >= rhs) as i64,
     }
 }
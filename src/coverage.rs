@@ -0,0 +1,347 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Which rules, branches and `cond` arms a corpus of inputs exercises.
+
+use crate::bytecode::Program;
+use crate::context::{ActionSource, Context, Step};
+use crate::im_info::ImInfo;
+use crate::json;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleCoverage {
+    /// The rule's KEYSEQ, or `None` if a later rule of the map has the same
+    /// KEYSEQ, so that this one can never match
+    pub keyseq: Option<String>,
+    pub hits: usize,
+    /// Times each `cond` arm of the rule's actions was taken, in the order
+    /// the arms appear
+    pub arms: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapCoverage {
+    pub name: String,
+    /// Rules in definition order
+    pub rules: Vec<RuleCoverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub map_name: String,
+    /// Times a rule was found through the branch, or for a `nil` branch,
+    /// times it ran
+    pub hits: usize,
+    /// Times each `cond` arm of the branch actions was taken
+    pub arms: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateCoverage {
+    pub name: String,
+    pub branches: Vec<BranchCoverage>,
+}
+
+/// Hit counts collected over a corpus by [`Coverage::add`], for the input
+/// method given to [`Coverage::new`].
+#[derive(Clone)]
+pub struct Coverage<'a> {
+    im_info: &'a ImInfo,
+    /// Number of inputs added
+    pub inputs: usize,
+    /// Keys that matched no rule and were committed as they are, whether
+    /// or not a `nil` branch ran first
+    pub unmatched_keys: usize,
+    /// Maps in name order
+    pub maps: Vec<MapCoverage>,
+    /// States in definition order
+    pub states: Vec<StateCoverage>,
+}
+
+impl fmt::Debug for Coverage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coverage")
+            .field("inputs", &self.inputs)
+            .field("unmatched_keys", &self.unmatched_keys)
+            .field("maps", &self.maps)
+            .field("states", &self.states)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> Coverage<'a> {
+    /// Coverage of `im_info` with nothing hit yet.
+    pub fn new(im_info: &'a ImInfo) -> Self {
+        let maps = im_info
+            .maps
+            .iter()
+            .map(|(name, map)| {
+                let mut rules: Vec<RuleCoverage> = map
+                    .rules
+                    .iter()
+                    .map(|program| RuleCoverage {
+                        keyseq: None,
+                        hits: 0,
+                        arms: vec![0; program.cond_arms().len()],
+                    })
+                    .collect();
+                for (keyseq, rule) in map.keyseqs() {
+                    if let Some(rule) = rules.get_mut(rule) {
+                        rule.keyseq = Some(keyseq);
+                    }
+                }
+                MapCoverage {
+                    name: name.clone(),
+                    rules,
+                }
+            })
+            .collect();
+
+        let states = im_info
+            .states
+            .iter()
+            .map(|state| StateCoverage {
+                name: state.name.clone(),
+                branches: state
+                    .branches
+                    .iter()
+                    .map(|branch| BranchCoverage {
+                        map_name: branch.map_name.clone(),
+                        hits: 0,
+                        arms: vec![0; branch.actions.cond_arms().len()],
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Coverage {
+            im_info,
+            inputs: 0,
+            unmatched_keys: 0,
+            maps,
+            states,
+        }
+    }
+
+    /// Convert `input` as [`convert`](crate::context::convert) does,
+    /// counting the rules, branches and `cond` arms it uses.
+    pub fn add(&mut self, input: &str) {
+        let im_info = self.im_info;
+        let mut ctx = Context::new(im_info.initial_state());
+        let keys: Vec<char> = input.chars().collect();
+        let mut i = 0;
+        self.inputs += 1;
+
+        while i < keys.len() {
+            let state_idx = im_info
+                .states
+                .iter()
+                .position(|state| state.id == ctx.current_state);
+            ctx.arms_taken = Some(Vec::new());
+            let step = ctx.step_or_commit(im_info, &keys[i..]);
+            let taken = ctx.arms_taken.take().unwrap_or_default();
+            i += step.consumed();
+
+            let branch = match step {
+                Step::Matched { found, .. } => {
+                    let map_idx = im_info.maps.keys().position(|name| name == found.map_name);
+                    if let Some(map_idx) = map_idx
+                        && let Some(program) = im_info.maps[found.map_name].rules.get(found.rule)
+                    {
+                        let rule = &mut self.maps[map_idx].rules[found.rule];
+                        rule.hits += 1;
+                        count_arms(program, &taken, ActionSource::Rule, &mut rule.arms);
+                    }
+                    found.branch
+                }
+                Step::Nil { branch, consumed } => {
+                    self.unmatched_keys += consumed;
+                    branch
+                }
+                Step::Unmatched => {
+                    self.unmatched_keys += 1;
+                    continue;
+                }
+            };
+
+            if let Some(state_idx) = state_idx {
+                let program = &im_info.states[state_idx].branches[branch].actions;
+                let coverage = &mut self.states[state_idx].branches[branch];
+                coverage.hits += 1;
+                count_arms(program, &taken, ActionSource::Branch, &mut coverage.arms);
+            }
+        }
+    }
+
+    /// Rules never matched, as (map name, rule index, KEYSEQ).
+    pub fn unused_rules(&self) -> Vec<(&str, usize, Option<&str>)> {
+        self.maps
+            .iter()
+            .flat_map(|map| {
+                map.rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rule)| rule.hits == 0)
+                    .map(|(idx, rule)| (map.name.as_str(), idx, rule.keyseq.as_deref()))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"inputs\":{},\"unmatched_keys\":{},\"maps\":[",
+            self.inputs, self.unmatched_keys
+        );
+        for (i, map) in self.maps.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            json::push_str(&mut out, &map.name);
+            out.push_str(",\"rules\":[");
+            for (j, rule) in map.rules.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{{\"rule\":{},\"keyseq\":", j);
+                json::push_opt_str(&mut out, rule.keyseq.as_deref());
+                let _ = write!(out, ",\"hits\":{},\"arms\":", rule.hits);
+                json::push_numbers(&mut out, &rule.arms);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        out.push_str("],\"states\":[");
+        for (i, state) in self.states.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            json::push_str(&mut out, &state.name);
+            out.push_str(",\"branches\":[");
+            for (j, branch) in state.branches.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{{\"branch\":{},\"map\":", j);
+                json::push_str(&mut out, &branch.map_name);
+                let _ = write!(out, ",\"hits\":{},\"arms\":", branch.hits);
+                json::push_numbers(&mut out, &branch.arms);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        out.push_str("],\"unused_rules\":[");
+        for (i, (map_name, rule, keyseq)) in self.unused_rules().into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"map\":");
+            json::push_str(&mut out, map_name);
+            let _ = write!(out, ",\"rule\":{},\"keyseq\":", rule);
+            json::push_opt_str(&mut out, keyseq);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Add the `cond` arms of `program` among `taken` to `arms`.
+fn count_arms(
+    program: &Program,
+    taken: &[(ActionSource, usize)],
+    source: ActionSource,
+    arms: &mut [usize],
+) {
+    let tests = program.cond_arms();
+    for &(_, test) in taken.iter().filter(|(from, _)| *from == source) {
+        if let Ok(arm) = tests.binary_search(&test)
+            && let Some(hits) = arms.get_mut(arm)
+        {
+            *hits += 1;
+        }
+    }
+}
+
+/// The report as text: hit counts per rule and branch, with `cond` arms
+/// below them, then the rules never hit.
+impl fmt::Display for Coverage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = self.maps.iter().flat_map(|map| &map.rules);
+        let branches = self.states.iter().flat_map(|state| &state.branches);
+        let arms = rules
+            .clone()
+            .flat_map(|rule| &rule.arms)
+            .chain(branches.clone().flat_map(|branch| &branch.arms));
+        writeln!(
+            f,
+            "{} inputs, {} unmatched keys: {}/{} rules, {}/{} branches, {}/{} cond arms hit",
+            self.inputs,
+            self.unmatched_keys,
+            rules.clone().filter(|rule| rule.hits > 0).count(),
+            rules.count(),
+            branches.clone().filter(|branch| branch.hits > 0).count(),
+            branches.count(),
+            arms.clone().filter(|&&hits| hits > 0).count(),
+            arms.count(),
+        )?;
+
+        for map in &self.maps {
+            writeln!(f, "\nmap {}", map.name)?;
+            for (idx, rule) in map.rules.iter().enumerate() {
+                match &rule.keyseq {
+                    Some(keyseq) => writeln!(f, "  {:>6}  {:>3} \"{}\"", rule.hits, idx, keyseq)?,
+                    None => writeln!(f, "  {:>6}  {:>3} (redefined)", rule.hits, idx)?,
+                }
+                write_arms(f, &rule.arms)?;
+            }
+        }
+        for state in &self.states {
+            writeln!(f, "\nstate {}", state.name)?;
+            for (idx, branch) in state.branches.iter().enumerate() {
+                writeln!(f, "  {:>6}  {:>3} {}", branch.hits, idx, branch.map_name)?;
+                write_arms(f, &branch.arms)?;
+            }
+        }
+
+        let unused = self.unused_rules();
+        if !unused.is_empty() {
+            writeln!(f, "\nnever hit:")?;
+            for (map_name, rule, keyseq) in unused {
+                match keyseq {
+                    Some(keyseq) => writeln!(f, "  {} {} \"{}\"", map_name, rule, keyseq)?,
+                    None => writeln!(f, "  {} {} (redefined)", map_name, rule)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_arms(f: &mut fmt::Formatter<'_>, arms: &[usize]) -> fmt::Result {
+    for (idx, hits) in arms.iter().enumerate() {
+        writeln!(f, "  {:>6}      cond arm {}", hits, idx)?;
+    }
+    Ok(())
+}
+
+/// Collect coverage of `im_info` over every input of `corpus`.
+pub fn collect_coverage<I>(im_info: &ImInfo, corpus: I) -> Coverage<'_>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut coverage = Coverage::new(im_info);
+    for input in corpus {
+        coverage.add(input.as_ref());
+    }
+    coverage
+}
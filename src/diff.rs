@@ -0,0 +1,404 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! What changed between two versions of an input method, rule by rule
+//! rather than line by line.
+
+use crate::bytecode::{Op, Program};
+use crate::context::convert;
+use crate::im_info::{ImInfo, State, Text};
+use crate::trace::describe_program;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A field that has a different value in the new version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// A rule whose KEYSEQ was added, removed or given other actions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleDiff {
+    pub keyseq: String,
+    pub change: Change,
+    /// The actions in the old version, as in a .mim file and separated by
+    /// commas, empty if the rule was added
+    pub before: String,
+    /// The actions in the new version, empty if the rule was removed
+    pub after: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapDiff {
+    pub name: String,
+    /// [`Change::Changed`] for a map in both versions with different rules
+    pub change: Change,
+    /// Rules in KEYSEQ order
+    pub rules: Vec<RuleDiff>,
+}
+
+/// A branch added, removed or given other actions. Branches are matched by
+/// map name, the first branch for a map in one version with the first in
+/// the other, and so on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchDiff {
+    pub map_name: String,
+    pub change: Change,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+    pub name: String,
+    pub change: Change,
+    pub title: Option<FieldChange>,
+    pub branches: Vec<BranchDiff>,
+    /// The map names of the branches in both versions, old and new order,
+    /// if the order changed. An earlier branch wins, so this can change
+    /// which rule fires.
+    pub order: Option<(Vec<String>, Vec<String>)>,
+}
+
+/// An input that converts to different text in the two versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputDiff {
+    pub input: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// The result of [`compare`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImDiff {
+    /// Changes to `lang`, `name`, `title`, `description` and `version`
+    pub metadata: Vec<FieldChange>,
+    /// Maps in name order
+    pub maps: Vec<MapDiff>,
+    /// States in the old version's order, then states added
+    pub states: Vec<StateDiff>,
+    /// Inputs whose output differs, in input order
+    pub inputs: Vec<InputDiff>,
+}
+
+impl ImDiff {
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty()
+            && self.maps.is_empty()
+            && self.states.is_empty()
+            && self.inputs.is_empty()
+    }
+}
+
+/// Compare `old` with `new`.
+///
+/// Rules are matched by KEYSEQ within each map, and actions compared with
+/// variables and states taken by name. Rules redefined later in their map
+/// never fire and are ignored.
+///
+/// Up to `samples` inputs that convert differently are also listed. They
+/// are taken from the KEYSEQs of the rules that changed, and all KEYSEQs
+/// of the maps of branches that changed, each typed from the initial
+/// state; pass 0 to skip this.
+pub fn compare(old: &ImInfo, new: &ImInfo, samples: usize) -> ImDiff {
+    let mut diff = ImDiff::default();
+
+    let fields = [
+        ("lang", &old.lang, &new.lang),
+        ("name", &old.name, &new.name),
+        ("title", &old.title.text, &new.title.text),
+        ("description", &old.description.text, &new.description.text),
+        ("version", &old.version, &new.version),
+    ];
+    for (field, before, after) in fields {
+        if before != after {
+            diff.metadata.push(FieldChange {
+                field: field.to_string(),
+                before: before.clone(),
+                after: after.clone(),
+            });
+        }
+    }
+
+    let names: BTreeSet<&String> = old.maps.keys().chain(new.maps.keys()).collect();
+    for name in names {
+        let before = rules(old, name);
+        let after = rules(new, name);
+        let keyseqs: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+
+        let rules: Vec<RuleDiff> = keyseqs
+            .into_iter()
+            .filter_map(|keyseq| {
+                let (change, before, after) =
+                    change(old, before.get(keyseq), new, after.get(keyseq))?;
+                Some(RuleDiff {
+                    keyseq: keyseq.clone(),
+                    change,
+                    before,
+                    after,
+                })
+            })
+            .collect();
+        let change = match (old.maps.contains_key(name), new.maps.contains_key(name)) {
+            (false, _) => Change::Added,
+            (_, false) => Change::Removed,
+            _ if rules.is_empty() => continue,
+            _ => Change::Changed,
+        };
+        diff.maps.push(MapDiff {
+            name: name.clone(),
+            change,
+            rules,
+        });
+    }
+
+    for state in &old.states {
+        let state_diff = match new.state(&state.name) {
+            Some(other) => compare_states(old, state, new, other),
+            None => Some(StateDiff {
+                name: state.name.clone(),
+                change: Change::Removed,
+                title: None,
+                branches: Vec::new(),
+                order: None,
+            }),
+        };
+        diff.states.extend(state_diff);
+    }
+    for state in &new.states {
+        if old.state(&state.name).is_none() {
+            diff.states.push(StateDiff {
+                name: state.name.clone(),
+                change: Change::Added,
+                title: None,
+                branches: Vec::new(),
+                order: None,
+            });
+        }
+    }
+
+    if samples > 0 {
+        diff.inputs = differing_inputs(old, new, &diff, samples);
+    }
+    diff
+}
+
+/// The live rules of map `name`, by KEYSEQ.
+fn rules<'a>(im_info: &'a ImInfo, name: &str) -> BTreeMap<String, &'a Program> {
+    let Some(map) = im_info.maps.get(name) else {
+        return BTreeMap::new();
+    };
+    map.keyseqs()
+        .into_iter()
+        .filter_map(|(keyseq, rule)| Some((keyseq, map.rules.get(rule)?)))
+        .collect()
+}
+
+/// How a rule or branch changed, with its actions before and after, or
+/// `None` if it did not.
+fn change(
+    old: &ImInfo,
+    before: Option<&&Program>,
+    new: &ImInfo,
+    after: Option<&&Program>,
+) -> Option<(Change, String, String)> {
+    match (before, after) {
+        (None, None) => None,
+        (None, Some(after)) => Some((Change::Added, String::new(), actions(new, after))),
+        (Some(before), None) => Some((Change::Removed, actions(old, before), String::new())),
+        (Some(before), Some(after)) => (!same_program(old, before, new, after))
+            .then(|| (Change::Changed, actions(old, before), actions(new, after))),
+    }
+}
+
+fn compare_states(old: &ImInfo, before: &State, new: &ImInfo, after: &State) -> Option<StateDiff> {
+    let title_text =
+        |title: &Option<Text>| title.as_ref().map(|t| t.text.clone()).unwrap_or_default();
+    let title = (before.title != after.title).then(|| FieldChange {
+        field: "title".to_string(),
+        before: title_text(&before.title),
+        after: title_text(&after.title),
+    });
+
+    let (old_branches, new_branches) = (branches(before), branches(after));
+    let keys: BTreeSet<&(String, usize)> = old_branches.keys().chain(new_branches.keys()).collect();
+    let branch_diffs: Vec<BranchDiff> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (change, before, after) =
+                change(old, old_branches.get(key), new, new_branches.get(key))?;
+            Some(BranchDiff {
+                map_name: key.0.clone(),
+                change,
+                before,
+                after,
+            })
+        })
+        .collect();
+
+    let common = |state: &State, other: &State| -> Vec<String> {
+        state
+            .branches
+            .iter()
+            .map(|branch| branch.map_name.clone())
+            .filter(|name| other.branches.iter().any(|b| b.map_name == *name))
+            .collect()
+    };
+    let (old_order, new_order) = (common(before, after), common(after, before));
+    let order = (old_order != new_order).then_some((old_order, new_order));
+
+    (title.is_some() || !branch_diffs.is_empty() || order.is_some()).then(|| StateDiff {
+        name: before.name.clone(),
+        change: Change::Changed,
+        title,
+        branches: branch_diffs,
+        order,
+    })
+}
+
+/// The branches of `state` by map name, numbered from 1 among those for
+/// the same map.
+fn branches(state: &State) -> BTreeMap<(String, usize), &Program> {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    state
+        .branches
+        .iter()
+        .map(|branch| {
+            let nth = seen.entry(&branch.map_name).or_default();
+            *nth += 1;
+            ((branch.map_name.clone(), *nth), &branch.actions)
+        })
+        .collect()
+}
+
+/// Whether two programs do the same, taking variables and states by name.
+fn same_program(old: &ImInfo, a: &Program, new: &ImInfo, b: &Program) -> bool {
+    let var = |im_info: &ImInfo, id| im_info.variables.name(id).unwrap_or_default().to_string();
+    a.ops.len() == b.ops.len()
+        && a.ops.iter().zip(&b.ops).all(|pair| match pair {
+            (Op::InsertVar(x), Op::InsertVar(y))
+            | (Op::Set(x), Op::Set(y))
+            | (Op::PushVar(x), Op::PushVar(y)) => var(old, *x) == var(new, *y),
+            (Op::Shift(x), Op::Shift(y)) => old.state_name(*x) == new.state_name(*y),
+            (x, y) => x == y,
+        })
+}
+
+fn actions(im_info: &ImInfo, program: &Program) -> String {
+    if program.is_empty() {
+        return "(no actions)".to_string();
+    }
+    describe_program(im_info, program).join(", ")
+}
+
+/// Up to `samples` inputs touched by `diff` that convert differently.
+fn differing_inputs(old: &ImInfo, new: &ImInfo, diff: &ImDiff, samples: usize) -> Vec<InputDiff> {
+    let mut candidates: BTreeSet<String> = diff
+        .maps
+        .iter()
+        .flat_map(|map| map.rules.iter().map(|rule| rule.keyseq.clone()))
+        .collect();
+    for branch in diff.states.iter().flat_map(|state| &state.branches) {
+        for im_info in [old, new] {
+            if let Some(map) = im_info.maps.get(&branch.map_name) {
+                candidates.extend(map.keyseqs().into_iter().map(|(keyseq, _)| keyseq));
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter_map(|input| {
+            let before = convert(old, &input);
+            let after = convert(new, &input);
+            (before != after).then_some(InputDiff {
+                input,
+                before,
+                after,
+            })
+        })
+        .take(samples)
+        .collect()
+}
+
+fn sign(change: Change) -> char {
+    match change {
+        Change::Added => '+',
+        Change::Removed => '-',
+        Change::Changed => '~',
+    }
+}
+
+/// The diff as text, with `+`, `-` and `~` marking what was added, removed
+/// and changed.
+impl fmt::Display for ImDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.metadata {
+            writeln!(
+                f,
+                "~ {}: \"{}\" -> \"{}\"",
+                field.field, field.before, field.after
+            )?;
+        }
+
+        for map in &self.maps {
+            writeln!(f, "{} map {}", sign(map.change), map.name)?;
+            for rule in &map.rules {
+                write!(f, "  {} \"{}\": ", sign(rule.change), rule.keyseq)?;
+                match rule.change {
+                    Change::Added => writeln!(f, "{}", rule.after)?,
+                    Change::Removed => writeln!(f, "{}", rule.before)?,
+                    Change::Changed => writeln!(f, "{} -> {}", rule.before, rule.after)?,
+                }
+            }
+        }
+
+        for state in &self.states {
+            writeln!(f, "{} state {}", sign(state.change), state.name)?;
+            if let Some(title) = &state.title {
+                writeln!(f, "  ~ title: \"{}\" -> \"{}\"", title.before, title.after)?;
+            }
+            for branch in &state.branches {
+                write!(f, "  {} branch {}: ", sign(branch.change), branch.map_name)?;
+                match branch.change {
+                    Change::Added => writeln!(f, "{}", branch.after)?,
+                    Change::Removed => writeln!(f, "{}", branch.before)?,
+                    Change::Changed => writeln!(f, "{} -> {}", branch.before, branch.after)?,
+                }
+            }
+            if let Some((before, after)) = &state.order {
+                writeln!(
+                    f,
+                    "  ~ branch order: {} -> {}",
+                    before.join(", "),
+                    after.join(", ")
+                )?;
+            }
+        }
+
+        if !self.inputs.is_empty() {
+            writeln!(f, "inputs that convert differently:")?;
+            for input in &self.inputs {
+                writeln!(
+                    f,
+                    "  \"{}\": \"{}\" -> \"{}\"",
+                    input.input, input.before, input.after
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Message translations for a single locale, loaded from a .po or .mo file.
+#[derive(Debug, Default, Clone)]
+pub struct Catalog {
+    messages: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Catalog::default()
+    }
+
+    pub fn insert(&mut self, msgid: &str, msgstr: &str) {
+        self.messages.insert(msgid.to_string(), msgstr.to_string());
+    }
+
+    pub fn get(&self, msgid: &str) -> Option<&str> {
+        self.messages.get(msgid).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Parse a gettext .po file.
+    ///
+    /// Fuzzy entries, entries with a `msgctxt` and untranslated entries are
+    /// skipped. For plural entries only `msgstr[0]` is kept.
+    pub fn from_po(text: &str) -> Catalog {
+        let mut catalog = Catalog::new();
+        let mut entry = PoEntry::default();
+        let mut current: Option<PoField> = None;
+
+        for line in text.lines() {
+            let line = line.trim();
+
+            if line.is_empty() {
+                entry.finish(&mut catalog);
+                current = None;
+                continue;
+            }
+
+            if let Some(flags) = line.strip_prefix("#,") {
+                if current.is_some() {
+                    entry.finish(&mut catalog);
+                    current = None;
+                }
+                if flags.split(',').any(|f| f.trim() == "fuzzy") {
+                    entry.fuzzy = true;
+                }
+                continue;
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            let (field, rest) = if let Some(rest) = line.strip_prefix("msgctxt") {
+                (Some(PoField::Context), rest)
+            } else if let Some(rest) = line.strip_prefix("msgid_plural") {
+                (Some(PoField::Ignored), rest)
+            } else if let Some(rest) = line.strip_prefix("msgid") {
+                (Some(PoField::Id), rest)
+            } else if let Some(rest) = line.strip_prefix("msgstr[0]") {
+                (Some(PoField::Str), rest)
+            } else if let Some(rest) = line.strip_prefix("msgstr[") {
+                (
+                    Some(PoField::Ignored),
+                    rest.split_once(']').map_or("", |(_, r)| r),
+                )
+            } else if let Some(rest) = line.strip_prefix("msgstr") {
+                (Some(PoField::Str), rest)
+            } else {
+                (None, line)
+            };
+
+            if let Some(field) = field {
+                // A new msgctxt or msgid starts the next entry
+                if matches!(field, PoField::Context | PoField::Id)
+                    && matches!(current, Some(PoField::Str | PoField::Ignored))
+                {
+                    entry.finish(&mut catalog);
+                }
+                current = Some(field);
+            }
+
+            let value = unquote_po(rest.trim());
+            match current {
+                Some(PoField::Context) => {
+                    entry.has_context = true;
+                }
+                Some(PoField::Id) => entry.msgid.push_str(&value),
+                Some(PoField::Str) => entry.msgstr.push_str(&value),
+                Some(PoField::Ignored) | None => {}
+            }
+        }
+        entry.finish(&mut catalog);
+
+        catalog
+    }
+
+    /// Parse a compiled gettext .mo file, in either byte order.
+    ///
+    /// Returns `None` if the data is not a valid .mo file.
+    pub fn from_mo(data: &[u8]) -> Option<Catalog> {
+        let read_u32 = |offset: usize, big_endian: bool| -> Option<u32> {
+            let bytes: [u8; 4] = data.get(offset..offset.checked_add(4)?)?.try_into().ok()?;
+            Some(if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            })
+        };
+
+        let big_endian = match read_u32(0, false)? {
+            0x950412de => false,
+            0xde120495 => true,
+            _ => return None,
+        };
+
+        let count = read_u32(8, big_endian)? as usize;
+        let ids_offset = read_u32(12, big_endian)? as usize;
+        let strs_offset = read_u32(16, big_endian)? as usize;
+
+        let read_string = |table: usize, index: usize| -> Option<&str> {
+            // Offsets come from the file, so they may overflow a 32-bit usize
+            let entry = index.checked_mul(8)?.checked_add(table)?;
+            let len = read_u32(entry, big_endian)? as usize;
+            let offset = read_u32(entry.checked_add(4)?, big_endian)? as usize;
+            core::str::from_utf8(data.get(offset..offset.checked_add(len)?)?).ok()
+        };
+
+        let mut catalog = Catalog::new();
+        for i in 0..count {
+            let msgid = read_string(ids_offset, i)?;
+            let msgstr = read_string(strs_offset, i)?;
+
+            // Skip the header entry and entries with a context
+            if msgid.is_empty() || msgid.contains('\u{4}') {
+                continue;
+            }
+
+            // Plural forms are separated by NUL; keep the singular
+            let msgid = msgid.split('\0').next().unwrap_or_default();
+            let msgstr = msgstr.split('\0').next().unwrap_or_default();
+            if !msgstr.is_empty() {
+                catalog.insert(msgid, msgstr);
+            }
+        }
+
+        Some(catalog)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PoField {
+    Context,
+    Id,
+    Str,
+    Ignored,
+}
+
+#[derive(Debug, Default)]
+struct PoEntry {
+    msgid: String,
+    msgstr: String,
+    fuzzy: bool,
+    has_context: bool,
+}
+
+impl PoEntry {
+    fn finish(&mut self, catalog: &mut Catalog) {
+        let entry = core::mem::take(self);
+        if !entry.fuzzy && !entry.has_context && !entry.msgid.is_empty() && !entry.msgstr.is_empty()
+        {
+            catalog.insert(&entry.msgid, &entry.msgstr);
+        }
+    }
+}
+
+fn unquote_po(s: &str) -> String {
+    let s = s.strip_prefix('"').unwrap_or(s);
+    let s = s.strip_suffix('"').unwrap_or(s);
+
+    // Octal escapes give bytes, which may be parts of a UTF-8 sequence
+    let mut result = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            result.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push(b'\n'),
+            Some('t') => result.push(b'\t'),
+            Some('r') => result.push(b'\r'),
+            Some('a') => result.push(0x07),
+            Some('b') => result.push(0x08),
+            Some('f') => result.push(0x0c),
+            Some('v') => result.push(0x0b),
+            Some(digit @ '0'..='7') => {
+                let mut value = digit as u32 - '0' as u32;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(next) => {
+                            value = value * 8 + next;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                result.push(value as u8);
+            }
+            Some(other) => {
+                let mut buf = [0; 4];
+                result.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+            None => {}
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// Catalogs for several locales, looked up with locale fallback.
+#[derive(Debug, Default, Clone)]
+pub struct Catalogs {
+    catalogs: BTreeMap<String, Catalog>,
+}
+
+impl Catalogs {
+    pub fn new() -> Self {
+        Catalogs::default()
+    }
+
+    pub fn insert(&mut self, locale: &str, catalog: Catalog) {
+        self.catalogs.insert(locale.to_string(), catalog);
+    }
+
+    pub fn get(&self, locale: &str) -> Option<&Catalog> {
+        self.catalogs.get(locale)
+    }
+
+    /// Translate `msgid` for `locale`, trying less specific locales in turn
+    /// (see [`locale_fallbacks`]). Returns `msgid` itself if no catalog has a
+    /// translation.
+    pub fn translate<'a>(&'a self, locale: &str, msgid: &'a str) -> &'a str {
+        locale_fallbacks(locale)
+            .iter()
+            .filter_map(|l| self.catalogs.get(l))
+            .find_map(|c| c.get(msgid))
+            .unwrap_or(msgid)
+    }
+}
+
+/// List the locales to try for `locale`, most specific first, following
+/// gettext: the codeset and territory are dropped before the modifier, so
+/// `bn_BD.UTF-8@latin` yields `bn_BD.UTF-8@latin`, `bn_BD@latin`,
+/// `bn@latin`, `bn_BD.UTF-8`, `bn_BD` and `bn`.
+pub fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let (rest, codeset) = match rest.split_once('.') {
+        Some((rest, codeset)) => (rest, Some(codeset)),
+        None => (rest, None),
+    };
+    let (language, territory) = match rest.split_once(['_', '-']) {
+        Some((language, territory)) => (language, Some(territory)),
+        None => (rest, None),
+    };
+
+    let mut result = Vec::new();
+    let mut push = |s: String| {
+        if !s.is_empty() && !result.contains(&s) {
+            result.push(s);
+        }
+    };
+
+    push(locale.to_string());
+    if let Some(modifier) = modifier {
+        if let Some(territory) = territory {
+            push(format!("{}_{}@{}", language, territory, modifier));
+        }
+        push(format!("{}@{}", language, modifier));
+    }
+    if let Some(territory) = territory {
+        if let Some(codeset) = codeset {
+            push(format!("{}_{}.{}", language, territory, codeset));
+        }
+        push(format!("{}_{}", language, territory));
+    }
+    push(language.to_string());
+
+    result
+}
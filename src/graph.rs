@@ -0,0 +1,257 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! The states of an input method and the `shift`s between them, as a graph
+//! for Graphviz or Mermaid.
+
+use crate::bytecode::*;
+use crate::im_info::ImInfo;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// What makes a `shift` happen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// The map actions of one rule
+    Rule {
+        map_name: String,
+        rule: usize,
+        keyseq: String,
+    },
+    /// The actions of a branch, run for any rule of its map, or the map
+    /// actions of its rules once the graph is [collapsed](StateGraph::collapsed)
+    Map(String),
+    /// The actions of a `nil` branch, run when no rule matches
+    Nil,
+}
+
+impl Trigger {
+    /// The edge label: the map name, followed by the KEYSEQ for a rule.
+    pub fn label(&self) -> String {
+        match self {
+            Trigger::Rule {
+                map_name, keyseq, ..
+            } => format!("{} \"{}\"", map_name, keyseq),
+            Trigger::Map(map_name) => map_name.clone(),
+            Trigger::Nil => "nil".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub trigger: Trigger,
+    /// Whether the `shift` is inside a `cond`, so that it may not happen
+    pub conditional: bool,
+}
+
+/// The result of [`state_graph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateGraph {
+    /// States in definition order, the initial state first, then states
+    /// shifted to that are not defined
+    pub states: Vec<String>,
+    /// Edges by the state they leave, in the order of its branches
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Build the graph of the states of `im_info`, with an edge for each
+/// `shift` in the map actions of a rule or in the actions of a branch.
+/// Rules whose KEYSEQ is redefined later in their map never fire and give
+/// no edges.
+pub fn state_graph(im_info: &ImInfo) -> StateGraph {
+    let mut graph = StateGraph {
+        states: im_info
+            .states
+            .iter()
+            .map(|state| state.name.clone())
+            .collect(),
+        edges: Vec::new(),
+    };
+
+    for state in &im_info.states {
+        for branch in &state.branches {
+            if branch.map_name == "nil" {
+                graph.add_shifts(im_info, &state.name, &branch.actions, &Trigger::Nil);
+                continue;
+            }
+
+            if let Some(map) = im_info.maps.get(&branch.map_name) {
+                let mut keyseqs = map.keyseqs();
+                keyseqs.sort_by_key(|(_, rule)| *rule);
+                for (keyseq, rule) in keyseqs {
+                    let Some(program) = map.rules.get(rule) else {
+                        continue;
+                    };
+                    let trigger = Trigger::Rule {
+                        map_name: branch.map_name.clone(),
+                        rule,
+                        keyseq,
+                    };
+                    graph.add_shifts(im_info, &state.name, program, &trigger);
+                }
+            }
+            let trigger = Trigger::Map(branch.map_name.clone());
+            graph.add_shifts(im_info, &state.name, &branch.actions, &trigger);
+        }
+    }
+
+    graph
+}
+
+impl StateGraph {
+    fn add_shifts(&mut self, im_info: &ImInfo, from: &str, program: &Program, trigger: &Trigger) {
+        for (idx, op) in program.ops.iter().enumerate() {
+            let Op::Shift(target) = op else {
+                continue;
+            };
+            let to = im_info.state_name(*target);
+            if !self.states.iter().any(|state| state == to) {
+                self.states.push(to.to_string());
+            }
+            self.push(GraphEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+                trigger: trigger.clone(),
+                conditional: in_cond(program, idx),
+            });
+        }
+    }
+
+    /// Add `edge`, or if there already is one with the same ends and
+    /// trigger, keep that one, conditional only if both are.
+    fn push(&mut self, edge: GraphEdge) {
+        match self.edges.iter_mut().find(|other| {
+            other.from == edge.from && other.to == edge.to && other.trigger == edge.trigger
+        }) {
+            Some(other) => other.conditional &= edge.conditional,
+            None => self.edges.push(edge),
+        }
+    }
+
+    /// The same graph with one edge per map instead of one per rule, for
+    /// input methods with many rules that shift.
+    pub fn collapsed(&self) -> StateGraph {
+        let mut graph = StateGraph {
+            states: self.states.clone(),
+            edges: Vec::new(),
+        };
+        for edge in &self.edges {
+            let trigger = match &edge.trigger {
+                Trigger::Rule { map_name, .. } => Trigger::Map(map_name.clone()),
+                trigger => trigger.clone(),
+            };
+            graph.push(GraphEdge {
+                trigger,
+                ..edge.clone()
+            });
+        }
+        graph
+    }
+
+    /// The graph in the Graphviz DOT language. The initial state has a
+    /// double border, and conditional edges are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph states {\n    rankdir=LR;\n");
+        for (idx, state) in self.states.iter().enumerate() {
+            let _ = write!(out, "    {}", dot_id(state));
+            if idx == 0 {
+                out.push_str(" [peripheries=2]");
+            }
+            out.push_str(";\n");
+        }
+        for edge in &self.edges {
+            let _ = write!(
+                out,
+                "    {} -> {} [label={}",
+                dot_id(&edge.from),
+                dot_id(&edge.to),
+                dot_id(&edge.trigger.label())
+            );
+            if edge.conditional {
+                out.push_str(", style=dashed");
+            }
+            out.push_str("];\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// The graph as a Mermaid flowchart. The initial state has rounded
+    /// ends, and conditional edges are dotted.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart LR\n");
+        for (idx, state) in self.states.iter().enumerate() {
+            let (open, close) = if idx == 0 { ("([", "])") } else { ("[", "]") };
+            let _ = writeln!(
+                out,
+                "    s{}{}\"{}\"{}",
+                idx,
+                open,
+                mermaid_text(state),
+                close
+            );
+        }
+        for edge in &self.edges {
+            let arrow = if edge.conditional { "-.->" } else { "-->" };
+            let _ = writeln!(
+                out,
+                "    s{} {}|\"{}\"| s{}",
+                self.node(&edge.from),
+                arrow,
+                mermaid_text(&edge.trigger.label()),
+                self.node(&edge.to)
+            );
+        }
+        out
+    }
+
+    fn node(&self, state: &str) -> usize {
+        self.states.iter().position(|s| s == state).unwrap_or(0)
+    }
+}
+
+/// Whether the op at `idx` is in an arm of a `cond`, that is, after the
+/// test of an arm that jumps past it.
+fn in_cond(program: &Program, idx: usize) -> bool {
+    program.ops[..idx]
+        .iter()
+        .any(|op| matches!(op, Op::JumpIfZero(target) if *target as usize > idx))
+}
+
+/// `text` as a quoted DOT ID.
+fn dot_id(text: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            '\n' => out.push_str("\\n"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `text` for inside a quoted Mermaid label, with characters that would end
+/// the label written as entity codes.
+fn mermaid_text(text: &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("#quot;"),
+            '#' => out.push_str("#35;"),
+            '\n' => out.push(' '),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
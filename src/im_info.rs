@@ -4,99 +4,515 @@
 // Licensed under the GNU Lesser General Public License v2.1 or later.
 // See the LICENSE file for full terms.
 
+use crate::bytecode::*;
+use crate::gettext::Catalogs;
 use crate::lisp_parser::Element;
-use fst::Map;
-use std::collections::HashMap;
+#[cfg(feature = "std")]
+use crate::lisp_parser::{ParseLimits, SectionReader};
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::fmt;
+#[cfg(feature = "std")]
+use core::ops::Range;
+#[cfg(feature = "std")]
+use fst::automaton::{Automaton, Str};
+#[cfg(feature = "std")]
+use fst::raw::{Fst, Node, Output};
+#[cfg(feature = "std")]
+use fst::{IntoStreamer, Map};
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+/// A string from the .mim file, which may be marked for translation with
+/// `(_ "...")`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Text {
+    pub text: String,
+    pub translatable: bool,
+}
+
+impl Text {
+    /// Look up the translation of this text for `locale`. Texts that are not
+    /// marked for translation are returned as is.
+    pub fn localize<'a>(&'a self, catalogs: &'a Catalogs, locale: &str) -> &'a str {
+        if self.translatable {
+            catalogs.translate(locale, &self.text)
+        } else {
+            &self.text
+        }
+    }
+}
+
+/// The bytes of a map's FST.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub enum FstData {
+    /// Built when the .mim file was loaded
+    Owned(Vec<u8>),
+    /// Embedded in the program, as by `include_bytes!`
+    Static(&'static [u8]),
+    /// A range of a snapshot buffer shared by all maps, such as a mapped file
+    Shared {
+        buffer: Arc<dyn AsRef<[u8]> + Send + Sync>,
+        range: Range<usize>,
+    },
+}
+
+#[cfg(feature = "std")]
+impl AsRef<[u8]> for FstData {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            FstData::Owned(bytes) => bytes,
+            FstData::Static(bytes) => bytes,
+            FstData::Shared { buffer, range } => &(**buffer).as_ref()[range.clone()],
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for FstData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FstData::Owned(_) => "Owned",
+            FstData::Static(_) => "Static",
+            FstData::Shared { .. } => "Shared",
+        };
+        write!(f, "{}({} bytes)", kind, self.as_ref().len())
+    }
+}
 
 #[derive(Debug)]
 pub struct KeySeqRuleMap {
-    pub fst: Map<Vec<u8>>,
-    pub rules: Vec<Element>,
+    /// KEYSEQs mapped to the index of their rule
+    #[cfg(feature = "std")]
+    pub fst: Map<FstData>,
+    /// KEYSEQs in byte order with the index of their rule, in place of the
+    /// FST when built without `std`
+    #[cfg(not(feature = "std"))]
+    pub keys: Vec<(Box<[u8]>, u32)>,
+    /// Compiled map actions of each rule, indexed by the FST value
+    pub rules: Vec<Program>,
+    /// Indices of the rules left out of the FST because their KEYSEQ has a
+    /// key code that is not a character, in increasing order
+    pub invalid_keyseqs: Vec<u32>,
+}
+
+impl KeySeqRuleMap {
+    /// Whether some KEYSEQ in this map is longer than `prefix` and starts
+    /// with it.
+    pub fn has_longer_keys(&self, prefix: &[u8]) -> bool {
+        let mut walker = self.walker();
+        prefix.iter().all(|&byte| walker.step_byte(byte)) && walker.can_continue()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn walker(&self) -> KeyWalker<'_> {
+        let fst = self.fst.as_fst();
+        KeyWalker {
+            fst,
+            node: fst.root(),
+            output: Output::zero(),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn walker(&self) -> KeyWalker<'_> {
+        KeyWalker {
+            keys: &self.keys,
+            depth: 0,
+        }
+    }
+
+    /// All KEYSEQs in byte order, each with the index of its rule. A rule
+    /// whose KEYSEQ is defined again later in the map is not among them.
+    #[cfg(feature = "std")]
+    pub fn keyseqs(&self) -> Vec<(String, usize)> {
+        self.fst
+            .stream()
+            .into_byte_vec()
+            .into_iter()
+            .map(|(key, rule)| (String::from_utf8_lossy(&key).into_owned(), rule as usize))
+            .collect()
+    }
+
+    /// All KEYSEQs in byte order, each with the index of its rule. A rule
+    /// whose KEYSEQ is defined again later in the map is not among them.
+    #[cfg(not(feature = "std"))]
+    pub fn keyseqs(&self) -> Vec<(String, usize)> {
+        self.keys
+            .iter()
+            .map(|(key, rule)| (String::from_utf8_lossy(key).into_owned(), *rule as usize))
+            .collect()
+    }
+
+    /// The KEYSEQs that start with `prefix`, including `prefix` itself, in
+    /// byte order, each with the index of its rule.
+    #[cfg(feature = "std")]
+    pub fn keyseqs_with_prefix(&self, prefix: &str) -> Vec<(String, usize)> {
+        self.fst
+            .search(Str::new(prefix).starts_with())
+            .into_stream()
+            .into_byte_vec()
+            .into_iter()
+            .map(|(key, rule)| (String::from_utf8_lossy(&key).into_owned(), rule as usize))
+            .collect()
+    }
+
+    /// The KEYSEQs that start with `prefix`, including `prefix` itself, in
+    /// byte order, each with the index of its rule.
+    #[cfg(not(feature = "std"))]
+    pub fn keyseqs_with_prefix(&self, prefix: &str) -> Vec<(String, usize)> {
+        let prefix = prefix.as_bytes();
+        let start = self.keys.partition_point(|(key, _)| **key < *prefix);
+        self.keys[start..]
+            .iter()
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, rule)| (String::from_utf8_lossy(key).into_owned(), *rule as usize))
+            .collect()
+    }
+
+    /// Find the longest KEYSEQ that `keys` starts with, in a single walk over
+    /// the FST. Returns its length in keys and the index of its rule.
+    pub fn longest_match(&self, keys: &[char]) -> Option<(usize, usize)> {
+        let mut walker = self.walker();
+        let mut best = None;
+
+        for (i, &key) in keys.iter().enumerate() {
+            if !walker.step(key) {
+                break;
+            }
+            if let Some(rule) = walker.rule() {
+                best = Some((i + 1, rule));
+            }
+            if !walker.can_continue() {
+                break;
+            }
+        }
+
+        best
+    }
+}
+
+/// Follows the keys typed so far through a map's FST, one key at a time.
+#[derive(Clone)]
+pub struct KeyWalker<'a> {
+    #[cfg(feature = "std")]
+    fst: &'a Fst<FstData>,
+    #[cfg(feature = "std")]
+    node: Node<'a>,
+    #[cfg(feature = "std")]
+    output: Output,
+    /// The KEYSEQs that start with the bytes followed so far
+    #[cfg(not(feature = "std"))]
+    keys: &'a [(Box<[u8]>, u32)],
+    /// Number of bytes followed so far
+    #[cfg(not(feature = "std"))]
+    depth: usize,
+}
+
+impl KeyWalker<'_> {
+    /// Follow `key`. Returns false, leaving the walker unchanged, if no
+    /// KEYSEQ continues with it.
+    pub fn step(&mut self, key: char) -> bool {
+        let mut buf = [0u8; 4];
+        let mut walker = self.clone();
+
+        if key
+            .encode_utf8(&mut buf)
+            .bytes()
+            .all(|byte| walker.step_byte(byte))
+        {
+            *self = walker;
+            true
+        } else {
+            false
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn step_byte(&mut self, byte: u8) -> bool {
+        let Some(i) = self.node.find_input(byte) else {
+            return false;
+        };
+        let transition = self.node.transition(i);
+        self.output = self.output.cat(transition.out);
+        self.node = self.fst.node(transition.addr);
+        true
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn step_byte(&mut self, byte: u8) -> bool {
+        // The keys share their first `depth` bytes and are sorted, so those
+        // that continue with `byte` are next to each other
+        let depth = self.depth;
+        let start = self
+            .keys
+            .partition_point(|(key, _)| key.get(depth).is_none_or(|&b| b < byte));
+        let end = self
+            .keys
+            .partition_point(|(key, _)| key.get(depth).is_none_or(|&b| b <= byte));
+        if start == end {
+            return false;
+        }
+        self.keys = &self.keys[start..end];
+        self.depth += 1;
+        true
+    }
+
+    /// Index of the rule whose KEYSEQ is exactly the keys followed so far.
+    #[cfg(feature = "std")]
+    pub fn rule(&self) -> Option<usize> {
+        self.node
+            .is_final()
+            .then(|| self.output.cat(self.node.final_output()).value() as usize)
+    }
+
+    /// Index of the rule whose KEYSEQ is exactly the keys followed so far.
+    #[cfg(not(feature = "std"))]
+    pub fn rule(&self) -> Option<usize> {
+        self.keys
+            .first()
+            .filter(|(key, _)| key.len() == self.depth)
+            .map(|&(_, rule)| rule as usize)
+    }
+
+    /// Whether some KEYSEQ is longer than the keys followed so far.
+    #[cfg(feature = "std")]
+    pub fn can_continue(&self) -> bool {
+        !self.node.is_empty()
+    }
+
+    /// Whether some KEYSEQ is longer than the keys followed so far.
+    #[cfg(not(feature = "std"))]
+    pub fn can_continue(&self) -> bool {
+        self.keys.last().is_some_and(|(key, _)| key.len() > self.depth)
+    }
 }
 
 #[derive(Debug)]
 pub struct Branch {
     pub map_name: String,
-    pub actions: Vec<Element>,
+    pub actions: Program,
 }
 
 #[derive(Debug)]
 pub struct State {
+    pub id: StateId,
     pub name: String,
+    /// Status title shown while this state is active, if it has its own.
+    pub title: Option<Text>,
     pub branches: Vec<Branch>,
 }
 
+#[derive(Debug, Default)]
 pub struct ImInfo {
     pub lang: String,
     pub name: String,
-    pub title: String,
-    pub description: String,
-    pub maps: HashMap<String, KeySeqRuleMap>,
+    pub title: Text,
+    pub description: Text,
+    pub version: String,
+    pub maps: BTreeMap<String, KeySeqRuleMap>,
     pub states: Vec<State>,
+    /// Variable slots used by the compiled actions
+    pub variables: Symbols,
+    /// Names of all states, defined or only referenced by `shift`
+    pub state_names: Symbols,
+}
+
+impl ImInfo {
+    /// The state the input method starts in.
+    pub fn initial_state(&self) -> StateId {
+        self.states.first().map_or(0, |s| s.id)
+    }
+
+    pub fn state(&self, name: &str) -> Option<&State> {
+        self.states.iter().find(|s| s.name == name)
+    }
+
+    pub fn state_by_id(&self, id: StateId) -> Option<&State> {
+        self.states.iter().find(|s| s.id == id)
+    }
+
+    pub fn state_name(&self, id: StateId) -> &str {
+        self.state_names.name(id).unwrap_or_default()
+    }
+
+    /// Title to show in the status indicator while state `id` is active:
+    /// the state's own title, or the input method title if it has none.
+    pub fn state_title(&self, id: StateId) -> &Text {
+        self.state_by_id(id)
+            .and_then(|s| s.title.as_ref())
+            .unwrap_or(&self.title)
+    }
+
+    /// File name of the icon for this input method, as looked up by m17n in
+    /// its `icons` directory: `LANG-NAME.png`, or `NAME.png` for input
+    /// methods not bound to a language (`t`).
+    pub fn icon_file_name(&self) -> String {
+        if self.lang == "t" || self.lang.is_empty() {
+            format!("{}.png", self.name)
+        } else {
+            format!("{}-{}.png", self.lang, self.name)
+        }
+    }
+
+    pub fn localized_title<'a>(&'a self, catalogs: &'a Catalogs, locale: &str) -> &'a str {
+        self.title.localize(catalogs, locale)
+    }
+
+    pub fn localized_description<'a>(&'a self, catalogs: &'a Catalogs, locale: &str) -> &'a str {
+        self.description.localize(catalogs, locale)
+    }
 }
 
 pub fn load_im_info(parsed_mim: Element) -> ImInfo {
-    let mut lang = String::new();
-    let mut name = String::new();
-    let mut title = String::new();
-    let mut description = String::new();
-    let mut maps = HashMap::new();
-    let mut states = Vec::new();
+    let mut im_info = ImInfo::default();
 
     if let Element::List(root) = parsed_mim {
         for element in root {
             if let Element::List(section) = element {
-                if section.is_empty() {
-                    continue;
-                }
+                load_section(&mut im_info, &section);
+            }
+        }
+    }
 
-                match &section[0] {
-                    Element::Symbol(sym) if sym == "input-method" => {
-                        if let Some(Element::Symbol(l)) = section.get(1) {
-                            lang = l.clone();
-                        }
-                        if let Some(Element::Symbol(n)) = section.get(2) {
-                            name = n.clone();
-                        }
-                    }
-                    Element::Symbol(sym) if sym == "description" => {
-                        if let Some(Element::List(desc_list)) = section.get(1) {
-                            if let Some(Element::Str(s)) = desc_list.get(1) {
-                                description = s.clone();
-                            }
-                        } else if let Some(Element::Str(s)) = section.get(1) {
-                            description = s.clone();
-                        }
-                    }
-                    Element::Symbol(sym) if sym == "title" => {
-                        if let Some(Element::Str(t)) = section.get(1) {
-                            title = t.clone();
-                        }
-                    }
-                    Element::Symbol(sym) if sym == "map" => {
-                        maps = parse_maps(&section[1..]);
-                    }
-                    Element::Symbol(sym) if sym == "state" => {
-                        states = parse_states(&section[1..]);
-                    }
-                    _ => {}
-                }
+    im_info
+}
+
+/// Load an input method from `reader`, one section at a time.
+///
+/// Each map and state definition is loaded as soon as it has been parsed, so
+/// neither the whole text nor the whole parsed tree is held in memory.
+#[cfg(feature = "std")]
+pub fn load_im_info_from_reader<R: Read>(reader: R) -> io::Result<ImInfo> {
+    load_im_info_from_reader_with_limits(reader, ParseLimits::default())
+}
+
+#[cfg(feature = "std")]
+pub fn load_im_info_from_reader_with_limits<R: Read>(
+    reader: R,
+    limits: ParseLimits,
+) -> io::Result<ImInfo> {
+    let mut im_info = ImInfo::default();
+
+    for section in SectionReader::with_limits(reader, limits) {
+        if let Element::List(section) = section? {
+            load_section(&mut im_info, &section);
+        }
+    }
+
+    Ok(im_info)
+}
+
+fn load_section(im_info: &mut ImInfo, section: &[Element]) {
+    if section.is_empty() {
+        return;
+    }
+
+    match &section[0] {
+        Element::Symbol(sym) if sym == "input-method" => {
+            if let Some(Element::Symbol(l)) = section.get(1) {
+                im_info.lang = l.clone();
+            }
+            if let Some(Element::Symbol(n)) = section.get(2) {
+                im_info.name = n.clone();
             }
         }
+        Element::Symbol(sym) if sym == "description" => {
+            if let Some(d) = section.get(1).and_then(element_to_text) {
+                im_info.description = d;
+            }
+        }
+        Element::Symbol(sym) if sym == "title" => {
+            if let Some(t) = section.get(1).and_then(element_to_text) {
+                im_info.title = t;
+            }
+        }
+        Element::Symbol(sym) if sym == "version" => {
+            if let Some(Element::Str(v)) = section.get(1) {
+                im_info.version = v.clone();
+            }
+        }
+        Element::Symbol(sym) if sym == "map" => {
+            let mut compiler = Compiler {
+                variables: &mut im_info.variables,
+                states: &mut im_info.state_names,
+            };
+            im_info.maps.extend(parse_maps(&section[1..], &mut compiler));
+        }
+        Element::Symbol(sym) if sym == "state" => {
+            let mut compiler = Compiler {
+                variables: &mut im_info.variables,
+                states: &mut im_info.state_names,
+            };
+            im_info.states.extend(parse_states(&section[1..], &mut compiler));
+        }
+        _ => {}
+    }
+}
+
+/// Read a string that is either plain `"..."` or marked for translation as
+/// `(_ "...")`.
+pub(crate) fn element_to_text(element: &Element) -> Option<Text> {
+    match element {
+        Element::Str(s) => Some(Text {
+            text: s.clone(),
+            translatable: false,
+        }),
+        Element::List(list) => match list.as_slice() {
+            [Element::Symbol(underscore), Element::Str(s)] if underscore == "_" => Some(Text {
+                text: s.clone(),
+                translatable: true,
+            }),
+            _ => None,
+        },
+        _ => None,
     }
+}
 
-    ImInfo {
-        lang,
-        name,
-        title,
-        description,
-        maps,
-        states,
+/// Index `rules` by their KEYSEQs, which are sorted and unique.
+#[cfg(feature = "std")]
+fn build_map(
+    sorted_keys: Vec<(usize, Vec<u8>)>,
+    rules: Vec<Program>,
+    invalid_keyseqs: Vec<u32>,
+) -> KeySeqRuleMap {
+    let mut builder = fst::MapBuilder::memory();
+    for (idx, key) in sorted_keys {
+        builder.insert(&key, idx as u64).unwrap();
     }
+
+    let fst_bytes = builder.into_inner().unwrap();
+    let fst = Map::new(FstData::Owned(fst_bytes)).unwrap();
+    KeySeqRuleMap { fst, rules, invalid_keyseqs }
+}
+
+/// Index `rules` by their KEYSEQs, which are sorted and unique.
+#[cfg(not(feature = "std"))]
+fn build_map(
+    sorted_keys: Vec<(usize, Vec<u8>)>,
+    rules: Vec<Program>,
+    invalid_keyseqs: Vec<u32>,
+) -> KeySeqRuleMap {
+    let keys = sorted_keys
+        .into_iter()
+        .map(|(idx, key)| (key.into_boxed_slice(), idx as u32))
+        .collect();
+    KeySeqRuleMap { keys, rules, invalid_keyseqs }
 }
 
-fn parse_maps(map_elements: &[Element]) -> HashMap<String, KeySeqRuleMap> {
-    let mut maps = HashMap::new();
+fn parse_maps(
+    map_elements: &[Element],
+    compiler: &mut Compiler,
+) -> BTreeMap<String, KeySeqRuleMap> {
+    let mut maps = BTreeMap::new();
 
     for element in map_elements {
         if let Element::List(map_def) = element {
@@ -107,6 +523,7 @@ fn parse_maps(map_elements: &[Element]) -> HashMap<String, KeySeqRuleMap> {
             if let Element::Symbol(map_name) = &map_def[0] {
                 let mut keys = Vec::new();
                 let mut values = Vec::new();
+                let mut invalid_keyseqs = Vec::new();
 
                 for rule in &map_def[1..] {
                     if let Element::List(rule_parts) = rule {
@@ -114,34 +531,31 @@ fn parse_maps(map_elements: &[Element]) -> HashMap<String, KeySeqRuleMap> {
                             continue;
                         }
 
-                        // KEYSEQ is the first element
-                        let keyseq = element_to_keyseq(&rule_parts[0]);
-
                         // MAP-ACTIONs are the remaining elements
-                        let actions = Element::List(rule_parts[1..].to_vec());
+                        let actions = compiler.compile(&rule_parts[1..]);
 
-                        keys.push(keyseq);
+                        // KEYSEQ is the first element. A rule whose KEYSEQ
+                        // cannot be typed keeps its index, so that the rules
+                        // after it do too, but no KEYSEQ leads to it
+                        match element_to_keyseq(&rule_parts[0]) {
+                            Some(keyseq) => keys.push((values.len(), keyseq)),
+                            None => invalid_keyseqs.push(values.len() as u32),
+                        }
                         values.push(actions);
                     }
                 }
 
-                // Build FST from keys
-                if !keys.is_empty() {
-                    let mut builder = fst::MapBuilder::memory();
-
-                    // Sort keys for FST building
-                    let mut indexed_keys: Vec<_> = keys.into_iter().enumerate().collect();
-                    indexed_keys.sort_by(|a, b| a.1.cmp(&b.1));
-
-                    for (idx, key) in indexed_keys {
-                        builder.insert(&key, idx as u64).unwrap();
-                    }
-
-                    let fst_bytes = builder.into_inner().unwrap();
-                    let fst = Map::new(fst_bytes).unwrap();
+                // Build FST from keys. An empty map is kept, so that it can
+                // be told apart from an undefined one.
+                // Sort keys for FST building; sorting is stable, so of rules
+                // with the same KEYSEQ the last one defined wins
+                let mut indexed_keys = keys;
+                indexed_keys.sort_by(|a, b| a.1.cmp(&b.1));
+                indexed_keys.reverse();
+                indexed_keys.dedup_by(|a, b| a.1 == b.1);
+                indexed_keys.reverse();
 
-                    maps.insert(map_name.clone(), KeySeqRuleMap { fst, rules: values });
-                }
+                maps.insert(map_name.clone(), build_map(indexed_keys, values, invalid_keyseqs));
             }
         }
     }
@@ -149,7 +563,7 @@ fn parse_maps(map_elements: &[Element]) -> HashMap<String, KeySeqRuleMap> {
     maps
 }
 
-fn parse_states(state_elements: &[Element]) -> Vec<State> {
+fn parse_states(state_elements: &[Element], compiler: &mut Compiler) -> Vec<State> {
     let mut states = Vec::new();
 
     for element in state_elements {
@@ -159,21 +573,26 @@ fn parse_states(state_elements: &[Element]) -> Vec<State> {
             }
 
             if let Element::Symbol(state_name) = &state_def[0] {
+                // An optional title comes before the branches
+                let title = state_def.get(1).and_then(element_to_text);
+                let branch_start = if title.is_some() { 2 } else { 1 };
+
                 // Store branches as a list
                 let mut branches = Vec::new();
-                for element in state_def[1..].to_vec() {
+                for element in &state_def[branch_start..] {
                     if let Element::List(branch_def) = element {
                         if branch_def.is_empty() {
                             continue;
                         }
 
                         if let Element::Symbol(map_name) = &branch_def[0] {
-                            let actions = branch_def[1..].to_vec();
+                            let actions = compiler.compile(&branch_def[1..]);
                             branches.push(Branch{ map_name: map_name.to_string(), actions });
                         }
                     }
                 }
-                states.push(State{ name: state_name.to_string(), branches });
+                let id = compiler.states.intern(state_name);
+                states.push(State{ id, name: state_name.to_string(), title, branches });
             }
         }
     }
@@ -181,18 +600,91 @@ fn parse_states(state_elements: &[Element]) -> Vec<State> {
     states
 }
 
-fn element_to_keyseq(element: &Element) -> Vec<u8> {
+/// The bytes of a KEYSEQ, or `None` if it has a key code that is not a
+/// character.
+fn element_to_keyseq(element: &Element) -> Option<Vec<u8>> {
     match element {
-        Element::Str(s) => s.as_bytes().to_vec(),
-        Element::Symbol(s) => s.as_bytes().to_vec(),
+        Element::Str(s) => Some(s.as_bytes().to_vec()),
         Element::List(list) => {
-            // Concatenate all elements in the list
+            // Concatenate all keys in the list
             let mut result = Vec::new();
             for item in list {
-                result.extend_from_slice(&element_to_keyseq(item));
+                result.extend_from_slice(&element_to_keyseq(item)?);
             }
-            result
+            Some(result)
         }
-        Element::Int(i) => i.to_string().as_bytes().to_vec(),
+        Element::Symbol(s) => match keysym_to_char(s) {
+            Some(c) => Some(c.to_string().into_bytes()),
+            None => Some(s.as_bytes().to_vec()),
+        },
+        // An integer key is a character code, e.g. ?ক or 0x0985
+        Element::Int(i) => u32::try_from(*i)
+            .ok()
+            .and_then(char::from_u32)
+            .map(|c| c.to_string().into_bytes()),
+    }
+}
+
+/// Map a key symbol to the character it types.
+///
+/// A symbol of a single character stands for that character, Unicode
+/// keysyms are written `U0985` (or `U+0985`) as in m17n-db, and the X
+/// keysym names of ASCII punctuation and a few control keys are accepted.
+/// Other symbols, such as keys with modifiers, have no character.
+pub fn keysym_to_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+
+    if let Some(hex) = name.strip_prefix("U+").or_else(|| name.strip_prefix('U'))
+        && (4..=6).contains(&hex.len())
+        && hex.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
     }
+
+    let c = match name {
+        "space" => ' ',
+        "exclam" => '!',
+        "quotedbl" => '"',
+        "numbersign" => '#',
+        "dollar" => '$',
+        "percent" => '%',
+        "ampersand" => '&',
+        "apostrophe" | "quoteright" => '\'',
+        "parenleft" => '(',
+        "parenright" => ')',
+        "asterisk" => '*',
+        "plus" => '+',
+        "comma" => ',',
+        "minus" => '-',
+        "period" => '.',
+        "slash" => '/',
+        "colon" => ':',
+        "semicolon" => ';',
+        "less" => '<',
+        "equal" => '=',
+        "greater" => '>',
+        "question" => '?',
+        "at" => '@',
+        "bracketleft" => '[',
+        "backslash" => '\\',
+        "bracketright" => ']',
+        "asciicircum" => '^',
+        "underscore" => '_',
+        "grave" | "quoteleft" => '`',
+        "braceleft" => '{',
+        "bar" => '|',
+        "braceright" => '}',
+        "asciitilde" => '~',
+        "BackSpace" => '\x08',
+        "Tab" => '\t',
+        "Linefeed" => '\n',
+        "Return" => '\r',
+        "Escape" => '\x1b',
+        "Delete" => '\x7f',
+        _ => return None,
+    };
+    Some(c)
 }
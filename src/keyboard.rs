@@ -0,0 +1,157 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! What each key of a keyboard types in each state, for labeling the keys
+//! of an on-screen keyboard.
+
+use crate::bytecode::StateId;
+use crate::context::Context;
+use crate::im_info::ImInfo;
+use crate::json;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// The keys of a US QWERTY keyboard, row by row, each without and with
+/// Shift.
+#[rustfmt::skip]
+pub const US_QWERTY: &[(char, char)] = &[
+    ('`', '~'), ('1', '!'), ('2', '@'), ('3', '#'), ('4', '$'), ('5', '%'), ('6', '^'),
+    ('7', '&'), ('8', '*'), ('9', '('), ('0', ')'), ('-', '_'), ('=', '+'),
+    ('q', 'Q'), ('w', 'W'), ('e', 'E'), ('r', 'R'), ('t', 'T'), ('y', 'Y'), ('u', 'U'),
+    ('i', 'I'), ('o', 'O'), ('p', 'P'), ('[', '{'), (']', '}'), ('\\', '|'),
+    ('a', 'A'), ('s', 'S'), ('d', 'D'), ('f', 'F'), ('g', 'G'), ('h', 'H'), ('j', 'J'),
+    ('k', 'K'), ('l', 'L'), (';', ':'), ('\'', '"'),
+    ('z', 'Z'), ('x', 'X'), ('c', 'C'), ('v', 'V'), ('b', 'B'), ('n', 'N'), ('m', 'M'),
+    (',', '<'), ('.', '>'), ('/', '?'),
+];
+
+/// What typing one key does in a state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyLabel {
+    pub key: char,
+    /// The text the rule for the key alone gives, or `None` if no rule has
+    /// the key alone as its KEYSEQ
+    pub output: Option<String>,
+    /// Whether a longer KEYSEQ starts with the key, so that its output may
+    /// still change as more keys are typed
+    pub starts_sequence: bool,
+    /// The state the rule shifts to, if it shifts to another state
+    pub next_state: Option<String>,
+}
+
+/// A key of the keyboard, without and with Shift.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutKey {
+    pub base: KeyLabel,
+    pub shifted: KeyLabel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateLayout {
+    pub state: String,
+    /// Keys in the order of the keyboard they were computed for
+    pub keys: Vec<LayoutKey>,
+}
+
+/// The result of [`keyboard_layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardLayout {
+    /// States in definition order
+    pub states: Vec<StateLayout>,
+}
+
+/// Compute what each of `keys`, given as pairs of the characters a key
+/// types without and with Shift, does in each state of `im_info`. Rules
+/// are looked up in the FSTs of the state's maps, the earliest branch
+/// winning, and run from an empty preedit, so an output that depends on
+/// earlier text is the one it has at the start of the input.
+pub fn keyboard_layout(im_info: &ImInfo, keys: &[(char, char)]) -> KeyboardLayout {
+    let states = im_info
+        .states
+        .iter()
+        .map(|state| StateLayout {
+            state: state.name.clone(),
+            keys: keys
+                .iter()
+                .map(|&(base, shifted)| LayoutKey {
+                    base: key_label(im_info, state.id, base),
+                    shifted: key_label(im_info, state.id, shifted),
+                })
+                .collect(),
+        })
+        .collect();
+    KeyboardLayout { states }
+}
+
+fn key_label(im_info: &ImInfo, state: StateId, key: char) -> KeyLabel {
+    let mut buf = [0u8; 4];
+    let prefix = key.encode_utf8(&mut buf).as_bytes();
+    let starts_sequence = im_info.state_by_id(state).is_some_and(|state| {
+        state
+            .branches
+            .iter()
+            .filter_map(|branch| im_info.maps.get(&branch.map_name))
+            .any(|map| map.has_longer_keys(prefix))
+    });
+
+    let mut ctx = Context::new(state);
+    let (output, next_state) = match ctx.find_match(im_info, &[key]) {
+        Some(found) => {
+            ctx.apply_match(im_info, &found);
+            ctx.commit();
+            let next_state = (ctx.current_state != state)
+                .then(|| im_info.state_name(ctx.current_state).to_string());
+            (Some(ctx.committed), next_state)
+        }
+        None => (None, None),
+    };
+
+    KeyLabel {
+        key,
+        output,
+        starts_sequence,
+        next_state,
+    }
+}
+
+impl KeyboardLayout {
+    /// The layout as JSON: an object per state with its `keys`, each an
+    /// object with the `base` and `shifted` labels.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"states\":[");
+        for (i, state) in self.states.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"state\":");
+            json::push_str(&mut out, &state.state);
+            out.push_str(",\"keys\":[");
+            for (j, key) in state.keys.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                out.push_str("{\"base\":");
+                push_label(&mut out, &key.base);
+                out.push_str(",\"shifted\":");
+                push_label(&mut out, &key.shifted);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn push_label(out: &mut String, label: &KeyLabel) {
+    out.push_str("{\"key\":");
+    json::push_str(out, label.key.encode_utf8(&mut [0u8; 4]));
+    out.push_str(",\"output\":");
+    json::push_opt_str(out, label.output.as_deref());
+    let _ = write!(out, ",\"starts_sequence\":{}", label.starts_sequence);
+    out.push_str(",\"next_state\":");
+    json::push_opt_str(out, label.next_state.as_deref());
+    out.push('}');
+}
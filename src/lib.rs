@@ -1,72 +1,322 @@
-mod im_info;
-mod lisp_parser;
-mod context;
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+// The cdylib needs a panic handler and an allocator. Targets with an OS
+// have std to provide them even when the `std` feature is off; on bare
+// metal, Cargo drops the cdylib instead.
+#[cfg(all(not(feature = "std"), not(target_os = "none")))]
+extern crate std;
+
+pub mod align;
+#[cfg(feature = "std")]
+pub mod batch;
+pub mod bytecode;
+pub mod char_names;
+pub mod cheatsheet;
+pub mod completion;
+pub mod conflicts;
+pub mod coverage;
+pub mod diff;
+pub mod gettext;
+pub mod graph;
+pub mod im_info;
+mod json;
+pub mod keyboard;
+pub mod lint;
+pub mod lisp_parser;
+pub mod reverse;
+pub mod roundtrip;
+pub mod context;
+pub mod session;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod stream;
+pub mod trace;
+
+use crate::align::*;
+use crate::char_names::*;
+use crate::cheatsheet::*;
+use crate::completion::*;
+use crate::gettext::*;
 use crate::im_info::*;
+use crate::keyboard::*;
 use crate::lisp_parser::*;
 use crate::context::*;
+use crate::session::*;
+use crate::trace::*;
+#[cfg(feature = "std")]
+use crate::snapshot::*;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct M17nMim {
-    im_info: ImInfo,
+    im_info: Arc<ImInfo>,
+    catalogs: Catalogs,
+    char_names: CharNames,
+    parse_error: Option<ParseError>,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl M17nMim {
-    #[wasm_bindgen(constructor)]
+    /// Load an input method from the text of a .mim file.
+    ///
+    /// If the text exceeds the default [`ParseLimits`], only what precedes
+    /// the limit is loaded, and [`M17nMim::parse_error`] says which limit
+    /// was exceeded and where. Use [`M17nMim::with_limits`], or `try_new`
+    /// from JavaScript, to reject such input instead.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(mim_str: &str) -> M17nMim {
         // parse the MIM string at creation
-        let list = parse_mim(mim_str);
+        let (list, parse_error) = parse_mim_partial(mim_str);
         let im_info = load_im_info(list);
-        M17nMim { im_info }
+        M17nMim { parse_error, ..M17nMim::from_im_info(im_info) }
+    }
+
+    /// Parse untrusted input with the default parse limits.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn try_new(mim_str: &str) -> Result<M17nMim, JsError> {
+        Ok(M17nMim::with_limits(mim_str, &ParseLimits::default())?)
     }
 
+    /// Load an input method from a snapshot made by [`M17nMim::to_snapshot`].
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen]
+    pub fn from_snapshot(bytes: &[u8]) -> Result<M17nMim, JsError> {
+        Ok(M17nMim::from_im_info(load_snapshot(bytes)?))
+    }
+
+    /// Serialize the loaded input method, for loading later without parsing.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        write_snapshot(&self.im_info)
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_lang(&self) -> String {
         self.im_info.lang.clone()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_name(&self) -> String {
         self.im_info.name.clone()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_title(&self) -> String {
-        self.im_info.title.clone()
+        self.im_info.title.text.clone()
     }
 
-    #[wasm_bindgen]
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
     pub fn get_description(&self) -> String {
-        self.im_info.description.clone()
+        self.im_info.description.text.clone()
     }
 
-    #[wasm_bindgen]
-    pub fn convert(&self, input: &str) -> String {
-        let mut ctx = Context::new(
-            self.im_info.states.first()
-                .map(|s| s.name.clone())
-                .unwrap_or_else(|| "init".to_string())
-        );
-
-        let input_chars: Vec<char> = input.chars().collect();
-        let mut i = 0;
-
-        while i < input_chars.len() {
-            let matched = ctx.process_key(&self.im_info, &input_chars[i..]);
-            if matched > 0 {
-                i += matched;
-            } else {
-                // No match, commit current preedit and add the character directly
-                ctx.commit();
-                ctx.committed.push(input_chars[i]);
-                i += 1;
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_version(&self) -> String {
+        self.im_info.version.clone()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_icon_file_name(&self) -> String {
+        self.im_info.icon_file_name()
+    }
+
+    /// Start a session that takes input one key at a time.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn create_session(&self) -> M17nSession {
+        M17nSession {
+            session: Session::new(&self.im_info),
+            im_info: Arc::clone(&self.im_info),
+        }
+    }
+
+    /// Add translations for `locale` from the text of a .po file.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_catalog_po(&mut self, locale: &str, po_str: &str) {
+        self.catalogs.insert(locale, Catalog::from_po(po_str));
+    }
+
+    /// Add translations for `locale` from a compiled .mo file. Returns false
+    /// if the data is not a valid .mo file.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn add_catalog_mo(&mut self, locale: &str, mo_bytes: &[u8]) -> bool {
+        match Catalog::from_mo(mo_bytes) {
+            Some(catalog) => {
+                self.catalogs.insert(locale, catalog);
+                true
             }
+            None => false,
         }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_localized_title(&self, locale: &str) -> String {
+        self.im_info.localized_title(&self.catalogs, locale).to_string()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_localized_description(&self, locale: &str) -> String {
+        self.im_info.localized_description(&self.catalogs, locale).to_string()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn convert(&self, input: &str) -> String {
+        convert(&self.im_info, input)
+    }
+
+    /// Like [`M17nMim::convert`], but fails if the input method loops on a
+    /// key instead of committing the key as it is.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen]
+    pub fn try_convert(&self, input: &str) -> Result<String, JsError> {
+        Ok(try_convert(&self.im_info, input)?)
+    }
+
+    /// Convert `input` and return the output with its alignment to the
+    /// input, as the JSON form of [`Conversion`]. Ranges count UTF-16 code
+    /// units, so they can index JavaScript strings directly.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn convert_aligned(&self, input: &str) -> String {
+        convert_aligned(&self.im_info, input).in_utf16(input).to_json()
+    }
+
+    /// Convert `input`, recording every step, and return the JSON form of
+    /// the [`Trace`].
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn trace(&self, input: &str) -> String {
+        trace_conversion(&self.im_info, input).to_json()
+    }
+
+    /// Like [`M17nMim::trace`], but as a text log.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn trace_text(&self, input: &str) -> String {
+        trace_conversion(&self.im_info, input).to_string()
+    }
+
+    /// Load Unicode character names for the cheat sheet from the text of
+    /// UnicodeData.txt.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn load_char_names(&mut self, unicode_data: &str) {
+        self.char_names = CharNames::from_unicode_data(unicode_data);
+    }
+
+    /// What to type for each output, as Markdown.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn cheat_sheet_markdown(&self) -> String {
+        cheat_sheet(&self.im_info).to_markdown(&self.char_names)
+    }
+
+    /// What to type for each output, as a printable HTML page.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn cheat_sheet_html(&self) -> String {
+        cheat_sheet(&self.im_info).to_html(&self.char_names)
+    }
+
+    /// What each key of a US QWERTY keyboard types in each state, as the
+    /// JSON form of [`KeyboardLayout`].
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn keyboard_layout(&self) -> String {
+        keyboard_layout(&self.im_info, US_QWERTY).to_json()
+    }
+
+    /// The KEYSEQs of state `state` that start with `pending`, shortest
+    /// first, as a JSON array of [`Completion`]s. Empty if there is no such
+    /// state.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn completions(&self, state: &str, pending: &str) -> String {
+        let pending: Vec<char> = pending.chars().collect();
+        let found = match self.im_info.state(state) {
+            Some(state) => completions(&self.im_info, state.id, &pending),
+            None => Vec::new(),
+        };
+        completions_to_json(&found)
+    }
+}
+
+impl M17nMim {
+    /// Like [`M17nMim::new`], but fails if the input exceeds `limits`
+    /// instead of dropping what is past them.
+    pub fn with_limits(mim_str: &str, limits: &ParseLimits) -> Result<M17nMim, ParseError> {
+        let list = parse_mim_with_limits(mim_str, limits)?;
+        let im_info = load_im_info(list);
+        Ok(M17nMim::from_im_info(im_info))
+    }
+
+    /// Wrap an already loaded input method, such as one from a snapshot, or
+    /// one already shared as an `Arc`, such as `include_mim!` gives.
+    pub fn from_im_info(im_info: impl Into<Arc<ImInfo>>) -> M17nMim {
+        M17nMim { im_info: im_info.into(), catalogs: Catalogs::new(), char_names: CharNames::new(), parse_error: None }
+    }
+
+    /// Why [`M17nMim::new`] loaded only part of the text: the parse limit
+    /// exceeded, and where.
+    pub fn parse_error(&self) -> Option<&ParseError> {
+        self.parse_error.as_ref()
+    }
+
+    /// The loaded input method, which can be shared with other threads.
+    pub fn im_info(&self) -> &Arc<ImInfo> {
+        &self.im_info
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct M17nSession {
+    im_info: Arc<ImInfo>,
+    session: Session,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl M17nSession {
+    /// Feed every character of `keys` as a separate key.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn handle_keys(&mut self, keys: &str) {
+        for key in keys.chars() {
+            self.session.handle_key(&self.im_info, key);
+        }
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn flush(&mut self) {
+        self.session.flush(&self.im_info);
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn take_committed(&mut self) -> String {
+        self.session.take_committed()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_preedit(&self) -> String {
+        self.session.preedit()
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_state(&self) -> String {
+        self.session.current_state_name(&self.im_info).to_string()
+    }
+
+    /// What can be typed after the held back keys, shortest first, as a
+    /// JSON array of [`Completion`]s.
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_completions(&self) -> String {
+        completions_to_json(&self.session.completions(&self.im_info))
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn get_status_title(&self) -> String {
+        self.session.status_title(&self.im_info).text.clone()
+    }
 
-        // Commit any remaining preedit
-        ctx.commit();
-        ctx.committed
+    #[cfg_attr(feature = "wasm", wasm_bindgen)]
+    pub fn reset(&mut self) {
+        self.session.reset(&self.im_info);
     }
 }
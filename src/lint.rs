@@ -0,0 +1,636 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Checks for mistakes in an input method that would otherwise only show up
+//! as wrong output.
+
+use crate::bytecode::*;
+use crate::im_info::{ImInfo, element_to_text, load_im_info};
+use crate::lisp_parser::{Element, ParseError, SourcePos, parse_mim_with_positions};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// What a [`Finding`] is about. Each kind has a code that stays the same
+/// from release to release, for filtering findings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// A branch names a map that is not defined
+    UndefinedMap,
+    /// A `shift` names a state that is not defined
+    UndefinedState,
+    /// `shift`s of `nil` branches or rules that push back all their keys
+    /// lead round in a cycle without consuming a key
+    InputLoop,
+    /// A state that no `shift` leads to from the initial state
+    UnreachableState,
+    /// A variable is read but never set
+    UnsetVariable,
+    /// A rule whose KEYSEQ is defined again later in the same map
+    RedefinedKeyseq,
+    /// A rule whose KEYSEQ an earlier branch of a state also matches
+    ShadowedKeyseq,
+    /// A map without rules
+    EmptyMap,
+    /// An action this implementation does not know
+    UnknownAction,
+    /// A `pushback` that does not give a number of keys, such as
+    /// `(pushback KEYSEQ)`, which this implementation does not run
+    UnsupportedAction,
+    /// A rule whose KEYSEQ has a key code that is not a character
+    InvalidKeyseq,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::UndefinedMap => "E001",
+            Code::UndefinedState => "E002",
+            Code::InputLoop => "E003",
+            Code::UnreachableState => "W001",
+            Code::UnsetVariable => "W002",
+            Code::RedefinedKeyseq => "W003",
+            Code::ShadowedKeyseq => "W004",
+            Code::EmptyMap => "W005",
+            Code::UnknownAction => "W006",
+            Code::UnsupportedAction => "W007",
+            Code::InvalidKeyseq => "W008",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Code::UndefinedMap | Code::UndefinedState | Code::InputLoop => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+/// Where in the .mim file a finding is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Map(String),
+    /// A map and the index of a rule in it, in definition order
+    Rule(String, usize),
+    State(String),
+    /// A state and the index of a branch in it
+    Branch(String, usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Map(map) => write!(f, "map {}", map),
+            Location::Rule(map, rule) => write!(f, "map {}, rule {}", map, rule),
+            Location::State(state) => write!(f, "state {}", state),
+            Location::Branch(state, branch) => write!(f, "state {}, branch {}", state, branch),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub code: Code,
+    pub location: Location,
+    /// Where in the text the map, rule, state or branch is defined, if the
+    /// findings came from [`lint_source`]
+    pub position: Option<SourcePos>,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(code: Code, location: Location, message: String) -> Self {
+        Finding {
+            severity: code.severity(),
+            code,
+            location,
+            position: None,
+            message,
+        }
+    }
+}
+
+/// A finding as `warning[W003] map m, rule 0: ...`, or with a position as
+/// `warning[W003] map m, rule 0 (line 3, column 6): ...`.
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}[{}] {}", severity, self.code.as_str(), self.location)?;
+        if let Some(pos) = self.position {
+            write!(f, " (line {}, column {})", pos.line, pos.column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check `im_info` for likely mistakes. Findings come grouped by kind, in
+/// the order of the [`Code`] variants.
+pub fn lint(im_info: &ImInfo) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    check_undefined_maps(im_info, &mut findings);
+    check_undefined_states(im_info, &mut findings);
+    check_input_loops(im_info, &mut findings);
+    check_unreachable_states(im_info, &mut findings);
+    check_unset_variables(im_info, &mut findings);
+    check_redefined_keyseqs(im_info, &mut findings);
+    check_shadowed_keyseqs(im_info, &mut findings);
+    check_empty_maps(im_info, &mut findings);
+    check_unknown_actions(im_info, &mut findings);
+    check_unsupported_actions(im_info, &mut findings);
+    check_invalid_keyseqs(im_info, &mut findings);
+    findings
+}
+
+/// Like [`lint`], but for the text of a .mim file, giving each finding the
+/// line and column of the map, rule, state or branch it is about. Fails if
+/// the text exceeds the default parse limits.
+pub fn lint_source(mim: &str) -> Result<Vec<Finding>, ParseError> {
+    let (parsed, positions) = parse_mim_with_positions(mim)?;
+    let sources = Sources::new(&parsed, &positions);
+    let mut findings = lint(&load_im_info(parsed));
+    for finding in &mut findings {
+        finding.position = sources.get(&finding.location);
+    }
+    Ok(findings)
+}
+
+/// Where each map and state is defined, with the positions of its rules or
+/// branches, indexed as [`load_im_info`] does.
+#[derive(Default)]
+struct Sources {
+    maps: BTreeMap<String, (SourcePos, Vec<SourcePos>)>,
+    states: BTreeMap<String, (SourcePos, Vec<SourcePos>)>,
+}
+
+impl Sources {
+    fn new(parsed: &Element, positions: &[SourcePos]) -> Self {
+        let mut sources = Sources::default();
+        let Element::List(root) = parsed else {
+            return sources;
+        };
+
+        // The top-level list is not in `positions`, so the first section is
+        // the first list that is
+        for (_, section, idx) in lists(root, 0) {
+            let Some(Element::Symbol(kind)) = section.first() else {
+                continue;
+            };
+            for (_, def, idx) in lists(section, idx + 1) {
+                let Some(Element::Symbol(name)) = def.first() else {
+                    continue;
+                };
+                if kind == "map" {
+                    let rules = lists(def, idx + 1)
+                        .filter(|(_, rule, _)| !rule.is_empty())
+                        .map(|(_, _, idx)| positions[idx])
+                        .collect();
+                    // A map defined again replaces the earlier definition
+                    sources.maps.insert(name.clone(), (positions[idx], rules));
+                } else if kind == "state" {
+                    // An optional title comes before the branches
+                    let branch_start = if def.get(1).and_then(element_to_text).is_some() {
+                        2
+                    } else {
+                        1
+                    };
+                    let branches = lists(def, idx + 1)
+                        .filter(|(at, branch, _)| {
+                            *at >= branch_start
+                                && matches!(branch.first(), Some(Element::Symbol(_)))
+                        })
+                        .map(|(_, _, idx)| positions[idx])
+                        .collect();
+                    sources
+                        .states
+                        .entry(name.clone())
+                        .or_insert((positions[idx], branches));
+                }
+            }
+        }
+        sources
+    }
+
+    fn get(&self, location: &Location) -> Option<SourcePos> {
+        match location {
+            Location::Map(map) => self.maps.get(map).map(|(pos, _)| *pos),
+            Location::Rule(map, rule) => self.maps.get(map)?.1.get(*rule).copied(),
+            Location::State(state) => self.states.get(state).map(|(pos, _)| *pos),
+            Location::Branch(state, branch) => self.states.get(state)?.1.get(*branch).copied(),
+        }
+    }
+}
+
+/// The lists among `elements`, each with its index in `elements` and the
+/// index of its position, where the first list among them has `first`.
+fn lists(elements: &[Element], first: usize) -> impl Iterator<Item = (usize, &[Element], usize)> {
+    let mut next = first;
+    elements
+        .iter()
+        .enumerate()
+        .filter_map(move |(at, element)| {
+            let idx = next;
+            next += count_lists(element);
+            match element {
+                Element::List(list) => Some((at, list.as_slice(), idx)),
+                _ => None,
+            }
+        })
+}
+
+fn count_lists(element: &Element) -> usize {
+    match element {
+        Element::List(list) => 1 + list.iter().map(count_lists).sum::<usize>(),
+        _ => 0,
+    }
+}
+
+/// Every compiled program with where it is: the map actions of each rule,
+/// then the actions of each branch.
+fn programs(im_info: &ImInfo) -> impl Iterator<Item = (Location, &Program)> {
+    let rules = im_info.maps.iter().flat_map(|(name, map)| {
+        map.rules
+            .iter()
+            .enumerate()
+            .map(move |(idx, program)| (Location::Rule(name.clone(), idx), program))
+    });
+    let branches =
+        im_info.states.iter().flat_map(|state| {
+            state.branches.iter().enumerate().map(move |(idx, branch)| {
+                (Location::Branch(state.name.clone(), idx), &branch.actions)
+            })
+        });
+    rules.chain(branches)
+}
+
+fn check_undefined_maps(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for state in &im_info.states {
+        for (idx, branch) in state.branches.iter().enumerate() {
+            // `nil` is the branch taken when no map matches, not a map
+            if branch.map_name != "nil" && !im_info.maps.contains_key(&branch.map_name) {
+                findings.push(Finding::new(
+                    Code::UndefinedMap,
+                    Location::Branch(state.name.clone(), idx),
+                    format!("map {} is not defined", branch.map_name),
+                ));
+            }
+        }
+    }
+}
+
+fn check_undefined_states(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (location, program) in programs(im_info) {
+        for op in &program.ops {
+            if let Op::Shift(state) = op
+                && im_info.state_by_id(*state).is_none()
+            {
+                findings.push(Finding::new(
+                    Code::UndefinedState,
+                    location.clone(),
+                    format!(
+                        "shift to state {}, which is not defined",
+                        im_info.state_name(*state)
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+fn check_input_loops(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    let edges = idle_edges(im_info);
+
+    // Report each cycle once, at the first step that enters it
+    let mut reported = BTreeSet::new();
+    for (from, to, location) in &edges {
+        let Some(path) = idle_path(&edges, *to, *from) else {
+            continue;
+        };
+        let mut states = path.clone();
+        states.sort_unstable();
+        states.dedup();
+        if !reported.insert(states) {
+            continue;
+        }
+
+        let mut names = vec![im_info.state_name(*from)];
+        names.extend(path.iter().map(|id| im_info.state_name(*id)));
+        findings.push(Finding::new(
+            Code::InputLoop,
+            location.clone(),
+            format!(
+                "{} consumes no key, so the same key can be handled again forever",
+                names.join(" -> ")
+            ),
+        ));
+    }
+}
+
+/// Steps that consume no key, as (from, to, where): the `shift`s of `nil`
+/// branches to other states, and rules whose actions push back all the
+/// keys of their KEYSEQ, to the states their actions shift to or to the
+/// same state.
+fn idle_edges(im_info: &ImInfo) -> Vec<(StateId, StateId, Location)> {
+    let mut edges = Vec::new();
+    for state in &im_info.states {
+        for (idx, branch) in state.branches.iter().enumerate() {
+            if branch.map_name == "nil" {
+                for target in shifts(&branch.actions) {
+                    if target != state.id {
+                        edges.push((state.id, target, Location::Branch(state.name.clone(), idx)));
+                    }
+                }
+                continue;
+            }
+
+            let Some(map) = im_info.maps.get(&branch.map_name) else {
+                continue;
+            };
+            for (keyseq, rule) in map.keyseqs() {
+                let Some(program) = map.rules.get(rule) else {
+                    continue;
+                };
+                let len = keyseq.chars().count();
+                let pushes_back_all = program.ops.iter().any(
+                    |op| matches!(op, Op::Pushback(keys) if *keys == 0 || *keys as usize >= len),
+                );
+                if !pushes_back_all {
+                    continue;
+                }
+
+                let mut targets: BTreeSet<StateId> =
+                    shifts(program).chain(shifts(&branch.actions)).collect();
+                if targets.is_empty() {
+                    targets.insert(state.id);
+                }
+                for target in targets {
+                    edges.push((
+                        state.id,
+                        target,
+                        Location::Rule(branch.map_name.clone(), rule),
+                    ));
+                }
+            }
+        }
+    }
+    edges
+}
+
+fn shifts(program: &Program) -> impl Iterator<Item = StateId> + '_ {
+    program.ops.iter().filter_map(|op| match op {
+        Op::Shift(target) => Some(*target),
+        _ => None,
+    })
+}
+
+/// The states along the shortest run of `edges` from `from` to `to`, both
+/// included.
+fn idle_path(
+    edges: &[(StateId, StateId, Location)],
+    from: StateId,
+    to: StateId,
+) -> Option<Vec<StateId>> {
+    let mut previous = BTreeMap::from([(from, from)]);
+    let mut pending = VecDeque::from([from]);
+    while let Some(id) = pending.pop_front() {
+        if id == to {
+            let mut path = vec![to];
+            let mut id = to;
+            while id != from {
+                id = previous[&id];
+                path.push(id);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for (_, next, _) in edges.iter().filter(|(start, _, _)| *start == id) {
+            if !previous.contains_key(next) {
+                previous.insert(*next, id);
+                pending.push_back(*next);
+            }
+        }
+    }
+    None
+}
+
+fn check_unreachable_states(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    let Some(initial) = im_info.states.first() else {
+        return;
+    };
+
+    let mut reached = BTreeSet::from([initial.id]);
+    let mut pending = vec![initial.id];
+    while let Some(id) = pending.pop() {
+        for target in shift_targets(im_info, id) {
+            if im_info.state_by_id(target).is_some() && reached.insert(target) {
+                pending.push(target);
+            }
+        }
+    }
+
+    for state in &im_info.states {
+        if !reached.contains(&state.id) {
+            findings.push(Finding::new(
+                Code::UnreachableState,
+                Location::State(state.name.clone()),
+                format!(
+                    "state {} cannot be reached from state {}",
+                    state.name, initial.name
+                ),
+            ));
+        }
+    }
+}
+
+/// States a `shift` in state `id` can lead to, from its branch actions or
+/// the map actions of its maps.
+pub(crate) fn shift_targets(im_info: &ImInfo, id: StateId) -> BTreeSet<StateId> {
+    let Some(state) = im_info.state_by_id(id) else {
+        return BTreeSet::new();
+    };
+
+    let rule_programs = state
+        .branches
+        .iter()
+        .filter_map(|branch| im_info.maps.get(&branch.map_name))
+        .flat_map(|map| &map.rules);
+    let branch_programs = state.branches.iter().map(|branch| &branch.actions);
+
+    rule_programs
+        .chain(branch_programs)
+        .flat_map(|program| &program.ops)
+        .filter_map(|op| match op {
+            Op::Shift(target) => Some(*target),
+            _ => None,
+        })
+        .collect()
+}
+
+fn check_unset_variables(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    let set: BTreeSet<VarId> = programs(im_info)
+        .flat_map(|(_, program)| &program.ops)
+        .filter_map(|op| match op {
+            Op::Set(var) => Some(*var),
+            _ => None,
+        })
+        .collect();
+
+    // Report each variable once, where it is first read
+    let mut reported = BTreeSet::new();
+    for (location, program) in programs(im_info) {
+        for op in &program.ops {
+            if let Op::PushVar(var) | Op::InsertVar(var) = op
+                && !set.contains(var)
+                && reported.insert(*var)
+            {
+                let name = im_info.variables.name(*var).unwrap_or_default();
+                findings.push(Finding::new(
+                    Code::UnsetVariable,
+                    location.clone(),
+                    format!("variable {} is read but never set, so it is always 0", name),
+                ));
+            }
+        }
+    }
+}
+
+fn check_redefined_keyseqs(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (name, map) in &im_info.maps {
+        let mut used = vec![false; map.rules.len()];
+        let rules = map
+            .keyseqs()
+            .into_iter()
+            .map(|(_, rule)| rule)
+            .chain(map.invalid_keyseqs.iter().map(|&rule| rule as usize));
+        for rule in rules {
+            if let Some(used) = used.get_mut(rule) {
+                *used = true;
+            }
+        }
+
+        for (idx, _) in used.iter().enumerate().filter(|(_, used)| !**used) {
+            findings.push(Finding::new(
+                Code::RedefinedKeyseq,
+                Location::Rule(name.clone(), idx),
+                "a later rule of the map has the same KEYSEQ, so this one never matches".into(),
+            ));
+        }
+    }
+}
+
+fn check_shadowed_keyseqs(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for state in &im_info.states {
+        for (idx, branch) in state.branches.iter().enumerate() {
+            let Some(map) = im_info.maps.get(&branch.map_name) else {
+                continue;
+            };
+            let earlier = &state.branches[..idx];
+
+            if let Some(same) = earlier
+                .iter()
+                .position(|other| other.map_name == branch.map_name)
+            {
+                findings.push(Finding::new(
+                    Code::ShadowedKeyseq,
+                    Location::Branch(state.name.clone(), idx),
+                    format!(
+                        "branch {} already uses map {}, so this branch never matches",
+                        same, branch.map_name
+                    ),
+                ));
+                continue;
+            }
+
+            for (keyseq, _) in map.keyseqs() {
+                let shadowing = earlier.iter().position(|other| {
+                    im_info.maps.get(&other.map_name).is_some_and(|other| {
+                        let mut walker = other.walker();
+                        keyseq.chars().all(|key| walker.step(key)) && walker.rule().is_some()
+                    })
+                });
+                if let Some(other) = shadowing {
+                    findings.push(Finding::new(
+                        Code::ShadowedKeyseq,
+                        Location::Branch(state.name.clone(), idx),
+                        format!(
+                            "KEYSEQ \"{}\" of map {} is also matched by map {} of branch {}, \
+                             which takes precedence",
+                            keyseq, branch.map_name, earlier[other].map_name, other
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn check_empty_maps(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (name, map) in &im_info.maps {
+        if map.rules.is_empty() {
+            findings.push(Finding::new(
+                Code::EmptyMap,
+                Location::Map(name.clone()),
+                format!("map {} has no rules", name),
+            ));
+        }
+    }
+}
+
+fn check_unknown_actions(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (location, program) in programs(im_info) {
+        for op in &program.ops {
+            // Only the form of `pushback` that gives a number of keys is
+            // compiled; the others are reported as unsupported
+            if let Op::Unknown(name) = op
+                && &**name != "pushback"
+            {
+                findings.push(Finding::new(
+                    Code::UnknownAction,
+                    location.clone(),
+                    format!("unknown action {}, which does nothing", name),
+                ));
+            }
+        }
+    }
+}
+
+fn check_unsupported_actions(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (location, program) in programs(im_info) {
+        for op in &program.ops {
+            if let Op::Unknown(name) = op
+                && &**name == "pushback"
+            {
+                findings.push(Finding::new(
+                    Code::UnsupportedAction,
+                    location.clone(),
+                    "pushback without a number of keys is not supported, so it does nothing".into(),
+                ));
+            }
+        }
+    }
+}
+
+fn check_invalid_keyseqs(im_info: &ImInfo, findings: &mut Vec<Finding>) {
+    for (name, map) in &im_info.maps {
+        for &rule in &map.invalid_keyseqs {
+            findings.push(Finding::new(
+                Code::InvalidKeyseq,
+                Location::Rule(name.clone(), rule as usize),
+                "a key code of the KEYSEQ is not a character, so the rule never matches".into(),
+            ));
+        }
+    }
+}
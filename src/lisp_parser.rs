@@ -4,6 +4,12 @@
 // Licensed under the GNU Lesser General Public License v2.1 or later.
 // See the LICENSE file for full terms.
 
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, BufRead, BufReader, Read};
+
 #[derive(Debug, Clone)]
 pub enum Element {
     List(Vec<Element>),
@@ -12,226 +18,719 @@ pub enum Element {
     Symbol(String),
 }
 
-/// Parse a Lisp-like expression from a string
-pub fn parse_mim(input: &str) -> Element {
-    let mut chars = input.chars().peekable();
-    let mut result = Vec::new();
+/// Limits that protect the parser against hostile input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum nesting depth of lists.
+    pub max_depth: usize,
+    /// Maximum length of a string or symbol, in characters.
+    pub max_string_len: usize,
+    /// Maximum number of rules over all maps.
+    pub max_rules: usize,
+    /// Maximum size of the input, in bytes.
+    pub max_total_size: usize,
+}
 
-    while let Some(_) = chars.peek() {
-        skip_whitespace_and_comments(&mut chars);
-        if chars.peek().is_none() {
-            break;
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_depth: 128,
+            max_string_len: 1 << 16,
+            max_rules: 1 << 20,
+            max_total_size: 64 << 20,
         }
-        if let Some(node) = parse_element(&mut chars) {
-            result.push(node);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    TooDeep,
+    StringTooLong,
+    TooManyRules,
+    TooLarge,
+    /// A list is not closed before the end of input
+    UnclosedList,
+    /// A `)` closes no list
+    UnexpectedClose,
+    /// A string is not closed before the end of input
+    UnterminatedString,
+}
+
+/// A parse limit was exceeded, or, when parsing strictly, the input is
+/// malformed. `line` and `column` are 1-based and point at the character
+/// where the limit was hit or the malformed list or string starts. `limit`
+/// is 0 for malformed input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub limit: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ParseErrorKind::TooDeep => "lists nested deeper than",
+            ParseErrorKind::StringTooLong => "string or symbol longer than",
+            ParseErrorKind::TooManyRules => "more map rules than",
+            ParseErrorKind::TooLarge => "input larger than",
+            ParseErrorKind::UnclosedList => "unclosed list",
+            ParseErrorKind::UnexpectedClose => "unexpected ')'",
+            ParseErrorKind::UnterminatedString => "unterminated string",
+        };
+        if self.limit == 0 {
+            write!(f, "{} at line {}, column {}", what, self.line, self.column)
+        } else {
+            write!(
+                f,
+                "{} {} at line {}, column {}",
+                what, self.limit, self.line, self.column
+            )
         }
     }
-    Element::List(result)
 }
 
-fn parse_element<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<Element> {
-    // Skip whitespace and comments
-    skip_whitespace_and_comments(chars);
+impl core::error::Error for ParseError {}
 
-    let c = chars.next()?;
+/// Parse a Lisp-like expression from a string, failing if the default
+/// [`ParseLimits`] are exceeded
+pub fn parse_mim(input: &str) -> Result<Element, ParseError> {
+    parse_mim_with_limits(input, &ParseLimits::default())
+}
 
-    match c {
-        '(' => parse_list(chars),
-        '"' => parse_string(chars),
-        '0'..='9' | '-' | '?' | '#' => parse_integer(chars, c),
-        ')' => None,
-        _ => parse_symbol(chars, c),
+/// Parse a Lisp-like expression from a string, stopping where the default
+/// [`ParseLimits`] are exceeded. What was parsed up to there is returned,
+/// with the error saying which limit was exceeded and where.
+pub fn parse_mim_partial(input: &str) -> (Element, Option<ParseError>) {
+    let mut parser = Parser::new(input.chars(), ParseLimits::default());
+    let result = parser.parse_all();
+    (result, parser.error)
+}
+
+/// Where a list starts in the input: the 1-based line and column of its
+/// opening parenthesis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePos {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Like [`parse_mim`], but also give the position of every list parsed, in
+/// the order the lists start, so that the list of the result visited `n`th
+/// in a depth-first walk, parents before their elements, starts at the
+/// `n`th position.
+pub fn parse_mim_with_positions(input: &str) -> Result<(Element, Vec<SourcePos>), ParseError> {
+    let mut parser = Parser::new(input.chars(), ParseLimits::default());
+    parser.positions = Some(Vec::new());
+    let result = parser.parse_all();
+
+    match parser.error {
+        Some(err) => Err(err),
+        None => Ok((result, parser.positions.unwrap_or_default())),
+    }
+}
+
+/// Parse a Lisp-like expression from a string, failing if `limits` are
+/// exceeded
+pub fn parse_mim_with_limits(input: &str, limits: &ParseLimits) -> Result<Element, ParseError> {
+    let mut parser = Parser::new(input.chars(), limits.clone());
+    let result = parser.parse_all();
+
+    match parser.error {
+        Some(err) => Err(err),
+        None => Ok(result),
     }
 }
 
-fn skip_whitespace_and_comments<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) {
-    loop {
-        // Skip whitespace
-        while chars.peek().map_or(false, |&c| c <= ' ') {
-            chars.next();
+/// Like [`parse_mim_with_limits`], but also fails on unbalanced parentheses
+/// and unterminated strings, which are otherwise closed at the end of input
+/// or ignored.
+pub fn parse_mim_strict(input: &str, limits: &ParseLimits) -> Result<Element, ParseError> {
+    let mut parser = Parser::new(input.chars(), limits.clone());
+    let result = parser.parse_all();
+
+    match parser.error.or(parser.malformed) {
+        Some(err) => Err(err),
+        None => Ok(result),
+    }
+}
+
+/// Parse a .mim file from a reader, yielding its top-level sections as they
+/// are parsed.
+///
+/// `map` and `state` sections are yielded one definition at a time, as
+/// `(map (MAP-NAME ...))` and `(state (STATE-NAME ...))`, so a large section
+/// is never held in memory as a whole. Exceeded limits are reported as
+/// [`io::ErrorKind::InvalidData`] errors wrapping a [`ParseError`].
+#[cfg(feature = "std")]
+pub struct SectionReader<R: Read> {
+    parser: Parser<CharReader<R>>,
+    split_section: Option<String>,
+    failed: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> SectionReader<R> {
+    pub fn new(reader: R) -> Self {
+        SectionReader::with_limits(reader, ParseLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: ParseLimits) -> Self {
+        let chars = CharReader {
+            reader: BufReader::new(reader),
+            error: None,
+            done: false,
+        };
+        SectionReader {
+            parser: Parser::new(chars, limits),
+            split_section: None,
+            failed: false,
         }
+    }
+
+    fn next_section(&mut self) -> Option<Element> {
+        let parser = &mut self.parser;
 
-        // Skip comments (from ';' to end of line)
-        if chars.peek() == Some(&';') {
-            chars.next();
-            while chars.peek().map_or(false, |&c| c != '\n') {
-                chars.next();
+        loop {
+            parser.skip_whitespace_and_comments();
+
+            if let Some(head) = &self.split_section {
+                if matches!(parser.peek(), Some(')') | None) {
+                    parser.next();
+                    parser.close_list();
+                    self.split_section = None;
+                    continue;
+                }
+                let head = head.clone();
+                if let Some(definition) = parser.parse_element() {
+                    return Some(Element::List(vec![Element::Symbol(head), definition]));
+                }
+                continue;
             }
-            if chars.peek() == Some(&'\n') {
-                chars.next();
+
+            if parser.peek()? != '(' {
+                if let Some(node) = parser.parse_element() {
+                    return Some(node);
+                }
+                continue;
             }
-        } else {
-            break;
+
+            parser.next();
+            if !parser.open_list() {
+                return None;
+            }
+            let Some(head) = parser.parse_element() else {
+                parser.close_list();
+                return Some(Element::List(Vec::new()));
+            };
+            parser.note_section_head(&head);
+            if let Element::Symbol(sym) = &head
+                && (sym == "map" || sym == "state")
+            {
+                self.split_section = Some(sym.clone());
+                continue;
+            }
+
+            let mut section = vec![head];
+            while let Some(element) = parser.parse_element() {
+                section.push(element);
+            }
+            parser.close_list();
+            return Some(Element::List(section));
         }
     }
 }
 
-fn parse_list<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<Element> {
-    let mut elements = Vec::new();
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for SectionReader<R> {
+    type Item = io::Result<Element>;
 
-    while let Some(element) = parse_element(chars) {
-        elements.push(element);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let section = self.next_section();
+        if let Some(err) = self.parser.chars.error.take() {
+            self.failed = true;
+            return Some(Err(err));
+        }
+        if let Some(err) = self.parser.error.take() {
+            self.failed = true;
+            return Some(Err(io::Error::new(io::ErrorKind::InvalidData, err)));
+        }
+        section.map(Ok)
     }
+}
 
-    Some(Element::List(elements))
+/// Decodes UTF-8 from a reader one character at a time. Errors end the
+/// iteration and are left in `error`.
+#[cfg(feature = "std")]
+struct CharReader<R: Read> {
+    reader: BufReader<R>,
+    error: Option<io::Error>,
+    done: bool,
 }
 
-fn parse_string<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Option<Element> {
-    let mut result = String::new();
+#[cfg(feature = "std")]
+impl<R: Read> CharReader<R> {
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buf) => {
+                    let byte = buf.first().copied();
+                    if byte.is_some() {
+                        self.reader.consume(1);
+                    }
+                    return Ok(byte);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> {
+        let Some(first) = self.next_byte()? else {
+            return Ok(None);
+        };
+
+        let len = match first {
+            0x00..=0x7f => return Ok(Some(first as char)),
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 0,
+        };
 
-    while let Some(c) = chars.next() {
-        if c == '"' {
-            return Some(Element::Str(result));
+        let mut bytes = [first, 0, 0, 0];
+        for byte in bytes.iter_mut().take(len).skip(1) {
+            *byte = self.next_byte()?.unwrap_or(0);
         }
 
-        if c == '\\' {
-            if let Some(escaped) = chars.next() {
-                if escaped == '\n' {
-                    continue; // Skip escaped newlines
-                }
+        std::str::from_utf8(&bytes[..len])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map(Some)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            })
+    }
+}
 
-                if escaped == 'x' || escaped == 'u' {
-                    // Parse hex unicode
-                    let code = parse_hex_number(chars);
-                    if let Some(ch) = char::from_u32(code as u32) {
-                        result.push(ch);
-                    }
-                    // Skip optional space after hex
-                    if chars.peek() == Some(&' ') {
-                        chars.next();
-                    }
-                } else {
-                    result.push(unescape_char(escaped));
-                }
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for CharReader<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.done {
+            return None;
+        }
+        match self.next_char() {
+            Ok(Some(c)) => Some(c),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                self.error = Some(err);
+                None
             }
-        } else {
-            result.push(c);
         }
     }
+}
 
-    Some(Element::Str(result))
+/// Parser state over a stream of characters.
+///
+/// Once a limit is exceeded the error is kept in `error` and the parser sees
+/// the end of input, so every parse function unwinds as it would at EOF.
+/// Malformed input is not an error: the first problem is kept in `malformed`
+/// and parsing goes on.
+struct Parser<I: Iterator<Item = char>> {
+    chars: I,
+    peeked: Option<Option<char>>,
+    limits: ParseLimits,
+    error: Option<ParseError>,
+    size: usize,
+    line: usize,
+    column: usize,
+    depth: usize,
+    rules: usize,
+    in_map: bool,
+    /// Whether the last `parse_element` stopped at a `)`
+    closed: bool,
+    malformed: Option<ParseError>,
+    /// If set, where each list starts is recorded here
+    positions: Option<Vec<SourcePos>>,
 }
 
-fn parse_integer<I: Iterator<Item = char>>(
-    chars: &mut std::iter::Peekable<I>,
-    first: char,
-) -> Option<Element> {
-    let num = match first {
-        '#' => {
-            if chars.peek() == Some(&'x') {
-                chars.next();
-                parse_hex_number(chars) as i64
-            } else {
-                // Not a hex number, parse as symbol instead
-                return parse_symbol(chars, first);
+impl<I: Iterator<Item = char>> Parser<I> {
+    fn new(chars: I, limits: ParseLimits) -> Self {
+        Parser {
+            chars,
+            peeked: None,
+            limits,
+            error: None,
+            size: 0,
+            line: 1,
+            column: 0,
+            depth: 0,
+            rules: 0,
+            in_map: false,
+            closed: false,
+            malformed: None,
+            positions: None,
+        }
+    }
+
+    fn fail(&mut self, kind: ParseErrorKind, limit: usize) {
+        if self.error.is_none() {
+            self.error = Some(ParseError {
+                kind,
+                limit,
+                line: self.line,
+                column: self.column,
+            });
+        }
+        self.peeked = Some(None);
+    }
+
+    fn note_malformed(&mut self, kind: ParseErrorKind, line: usize, column: usize) {
+        if self.malformed.is_none() {
+            self.malformed = Some(ParseError {
+                kind,
+                limit: 0,
+                line,
+                column,
+            });
+        }
+    }
+
+    fn pull(&mut self) -> Option<char> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+
+        self.size += c.len_utf8();
+        if self.size > self.limits.max_total_size {
+            self.fail(ParseErrorKind::TooLarge, self.limits.max_total_size);
+            return None;
+        }
+        Some(c)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.pull());
+        }
+        self.peeked.flatten()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        match self.peeked.take() {
+            Some(c) => c,
+            None => self.pull(),
+        }
+    }
+
+    /// Enter a list, checking the depth and rule limits.
+    fn open_list(&mut self) -> bool {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            self.fail(ParseErrorKind::TooDeep, self.limits.max_depth);
+            return false;
+        }
+
+        // Rules are the lists inside (map (MAP-NAME RULE ...))
+        if self.in_map && self.depth == 3 {
+            self.rules += 1;
+            if self.rules > self.limits.max_rules {
+                self.fail(ParseErrorKind::TooManyRules, self.limits.max_rules);
+                return false;
             }
         }
-        '0' => {
-            if chars.peek() == Some(&'x') {
-                chars.next();
-                parse_hex_number(chars) as i64
-            } else {
-                parse_decimal(chars, '0')
+        true
+    }
+
+    fn close_list(&mut self) {
+        if self.depth == 1 {
+            self.in_map = false;
+        }
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    fn note_section_head(&mut self, head: &Element) {
+        if self.depth == 1 {
+            self.in_map = matches!(head, Element::Symbol(sym) if sym == "map");
+        }
+    }
+
+    fn push_checked(&mut self, result: &mut String, len: &mut usize, c: char) {
+        *len += 1;
+        if *len > self.limits.max_string_len {
+            self.fail(ParseErrorKind::StringTooLong, self.limits.max_string_len);
+            return;
+        }
+        result.push(c);
+    }
+
+    /// Parse the top-level elements up to the end of input.
+    fn parse_all(&mut self) -> Element {
+        let mut result = Vec::new();
+
+        while self.peek().is_some() {
+            self.skip_whitespace_and_comments();
+            if self.peek().is_none() {
+                break;
+            }
+            if let Some(node) = self.parse_element() {
+                result.push(node);
+            } else if self.closed {
+                self.note_malformed(ParseErrorKind::UnexpectedClose, self.line, self.column);
             }
         }
-        '?' => {
-            // Character literal
-            parse_char_literal(chars)
+
+        Element::List(result)
+    }
+
+    fn parse_element(&mut self) -> Option<Element> {
+        self.closed = false;
+
+        // Skip whitespace and comments
+        self.skip_whitespace_and_comments();
+
+        let c = self.next()?;
+
+        match c {
+            '(' => self.parse_list(),
+            '"' => self.parse_string(),
+            '0'..='9' | '-' | '?' | '#' => self.parse_integer(c),
+            ')' => {
+                self.closed = true;
+                None
+            }
+            _ => self.parse_symbol(c),
         }
-        '-' => {
-            if let Some(&c) = chars.peek() {
-                if c.is_ascii_digit() {
-                    chars.next();
-                    -parse_decimal(chars, c)
-                } else {
-                    return parse_symbol(chars, first);
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            // Skip whitespace
+            while self.peek().is_some_and(|c| c <= ' ') {
+                self.next();
+            }
+
+            // Skip comments (from ';' to end of line)
+            if self.peek() == Some(';') {
+                self.next();
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.next();
+                }
+                if self.peek() == Some('\n') {
+                    self.next();
                 }
             } else {
-                return parse_symbol(chars, first);
+                break;
             }
         }
-        _ => parse_decimal(chars, first),
-    };
-
-    Some(Element::Int(num))
-}
+    }
 
-fn parse_decimal<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>, first: char) -> i64 {
-    let mut num = (first as u8 - b'0') as i64;
+    fn parse_list(&mut self) -> Option<Element> {
+        let (line, column) = (self.line, self.column);
+        if !self.open_list() {
+            return None;
+        }
+        if let Some(positions) = &mut self.positions {
+            positions.push(SourcePos { line, column });
+        }
 
-    while let Some(&c) = chars.peek() {
-        if c.is_ascii_digit() {
-            chars.next();
-            num = num * 10 + (c as u8 - b'0') as i64;
-        } else {
-            break;
+        let mut elements = Vec::new();
+        while let Some(element) = self.parse_element() {
+            if elements.is_empty() {
+                self.note_section_head(&element);
+            }
+            elements.push(element);
         }
+        if !self.closed {
+            self.note_malformed(ParseErrorKind::UnclosedList, line, column);
+        }
+
+        self.close_list();
+        Some(Element::List(elements))
     }
 
-    num
-}
+    fn parse_string(&mut self) -> Option<Element> {
+        let (line, column) = (self.line, self.column);
+        let mut result = String::new();
+        let mut len = 0;
+
+        while let Some(c) = self.next() {
+            if c == '"' {
+                return Some(Element::Str(result));
+            }
+
+            if c == '\\' {
+                if let Some(escaped) = self.next() {
+                    if escaped == '\n' {
+                        continue; // Skip escaped newlines
+                    }
+
+                    if escaped == 'x' || escaped == 'u' {
+                        // Parse hex unicode
+                        let code = self.parse_hex_number();
+                        if let Some(ch) = char::from_u32(code) {
+                            self.push_checked(&mut result, &mut len, ch);
+                        }
+                        // Skip optional space after hex
+                        if self.peek() == Some(' ') {
+                            self.next();
+                        }
+                    } else {
+                        self.push_checked(&mut result, &mut len, unescape_char(escaped));
+                    }
+                }
+            } else {
+                self.push_checked(&mut result, &mut len, c);
+            }
+        }
 
-fn parse_hex_number<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> u32 {
-    let mut num = 0u32;
+        self.note_malformed(ParseErrorKind::UnterminatedString, line, column);
+        Some(Element::Str(result))
+    }
 
-    while let Some(&c) = chars.peek() {
-        let digit = match c {
-            '0'..='9' => c as u32 - '0' as u32,
-            'A'..='F' => c as u32 - 'A' as u32 + 10,
-            'a'..='f' => c as u32 - 'a' as u32 + 10,
-            _ => break,
+    fn parse_integer(&mut self, first: char) -> Option<Element> {
+        let num = match first {
+            '#' => {
+                if self.peek() == Some('x') {
+                    self.next();
+                    self.parse_hex_number() as i64
+                } else {
+                    // Not a hex number, parse as symbol instead
+                    return self.parse_symbol(first);
+                }
+            }
+            '0' => {
+                if self.peek() == Some('x') {
+                    self.next();
+                    self.parse_hex_number() as i64
+                } else {
+                    self.parse_decimal('0')
+                }
+            }
+            '?' => {
+                // Character literal
+                self.parse_char_literal()
+            }
+            '-' => {
+                if let Some(c) = self.peek() {
+                    if c.is_ascii_digit() {
+                        self.next();
+                        self.parse_decimal(c).wrapping_neg()
+                    } else {
+                        return self.parse_symbol(first);
+                    }
+                } else {
+                    return self.parse_symbol(first);
+                }
+            }
+            _ => self.parse_decimal(first),
         };
-        chars.next();
-        num = (num << 4) | digit;
+
+        Some(Element::Int(num))
     }
 
-    num
-}
+    fn parse_decimal(&mut self, first: char) -> i64 {
+        let mut num = (first as u8 - b'0') as i64;
 
-fn parse_char_literal<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> i64 {
-    if let Some(c) = chars.next() {
-        if c == '\\' {
-            if let Some(escaped) = chars.next() {
-                unescape_char(escaped) as i64
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.next();
+                num = num.wrapping_mul(10).wrapping_add((c as u8 - b'0') as i64);
             } else {
-                '\\' as i64
+                break;
             }
-        } else {
-            c as i64
         }
-    } else {
-        0
+
+        num
     }
-}
 
-fn parse_symbol<I: Iterator<Item = char>>(
-    chars: &mut std::iter::Peekable<I>,
-    first: char,
-) -> Option<Element> {
-    let mut result = String::new();
-    result.push(if first == '\\' {
-        chars.next().map(unescape_char).unwrap_or('\\')
-    } else {
-        first
-    });
+    fn parse_hex_number(&mut self) -> u32 {
+        let mut num = 0u32;
 
-    while let Some(&c) = chars.peek() {
-        if c <= ' ' || c == ')' || c == '(' || c == '"' {
-            break;
+        while let Some(c) = self.peek() {
+            let digit = match c {
+                '0'..='9' => c as u32 - '0' as u32,
+                'A'..='F' => c as u32 - 'A' as u32 + 10,
+                'a'..='f' => c as u32 - 'a' as u32 + 10,
+                _ => break,
+            };
+            self.next();
+            num = (num << 4) | digit;
         }
 
-        chars.next();
+        num
+    }
 
-        if c == '\\' {
-            if let Some(escaped) = chars.next() {
-                result.push(unescape_char(escaped));
+    fn parse_char_literal(&mut self) -> i64 {
+        if let Some(c) = self.next() {
+            if c == '\\' {
+                match self.next() {
+                    // Hex escapes such as ?\x0985, as in strings
+                    Some('x' | 'u') if self.peek().is_some_and(|c| c.is_ascii_hexdigit()) => {
+                        self.parse_hex_number() as i64
+                    }
+                    Some(escaped) => unescape_char(escaped) as i64,
+                    None => '\\' as i64,
+                }
+            } else {
+                c as i64
             }
         } else {
-            result.push(c);
+            0
         }
     }
 
-    Some(Element::Symbol(result))
+    fn parse_symbol(&mut self, first: char) -> Option<Element> {
+        let mut result = String::new();
+        let mut len = 0;
+        let first = if first == '\\' {
+            self.next().map(unescape_char).unwrap_or('\\')
+        } else {
+            first
+        };
+        self.push_checked(&mut result, &mut len, first);
+
+        while let Some(c) = self.peek() {
+            if c <= ' ' || c == ')' || c == '(' || c == '"' {
+                break;
+            }
+
+            self.next();
+
+            if c == '\\' {
+                if let Some(escaped) = self.next() {
+                    self.push_checked(&mut result, &mut len, unescape_char(escaped));
+                }
+            } else {
+                self.push_checked(&mut result, &mut len, c);
+            }
+        }
+
+        Some(Element::Symbol(result))
+    }
 }
 
 fn unescape_char(c: char) -> char {
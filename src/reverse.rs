@@ -0,0 +1,209 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Finding what to type to get a given text.
+
+use crate::bytecode::{Op, StateId};
+use crate::im_info::ImInfo;
+use crate::session::Session;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Bounds on the search done by [`keys_for`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchLimits {
+    /// Maximum number of keys in a key sequence.
+    pub max_keys: usize,
+    /// Maximum number of key sequences returned.
+    pub max_results: usize,
+    /// Maximum number of distinct input method states visited.
+    pub max_states: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            max_keys: 16,
+            max_results: 8,
+            max_states: 1 << 16,
+        }
+    }
+}
+
+/// The result of [`keys_for`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeysFor {
+    /// The shortest key sequences that convert to the text, all of the
+    /// same length
+    Found(Vec<String>),
+    /// No key sequence of at most [`SearchLimits::max_keys`] keys converts
+    /// to the text
+    NotTypeable,
+    /// The search visited [`SearchLimits::max_states`] states without
+    /// finding a key sequence
+    LimitReached,
+}
+
+/// What a session has done so far, apart from the keys that led to it.
+/// Sessions with the same key give the same output for any further keys.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SessionKey {
+    committed: usize,
+    preedit: Vec<char>,
+    cursor: usize,
+    variables: Vec<i64>,
+    state: StateId,
+    pending: Vec<char>,
+}
+
+impl SessionKey {
+    pub(crate) fn new(session: &Session) -> Self {
+        let ctx = session.context();
+        let mut variables = ctx.variables.clone();
+        while variables.last() == Some(&0) {
+            variables.pop();
+        }
+        SessionKey {
+            committed: ctx.committed.len(),
+            preedit: ctx.preedit.clone(),
+            cursor: ctx.current_pos,
+            variables,
+            state: ctx.current_state,
+            pending: session.pending().to_vec(),
+        }
+    }
+}
+
+/// A session reached by typing some keys, with the key sequences that
+/// reach it.
+struct Reached {
+    session: Session,
+    paths: Vec<String>,
+    converts_to_target: bool,
+}
+
+/// Find the shortest key sequences whose [`convert`](crate::context::convert)
+/// output is `target`.
+///
+/// Key sequences are searched breadth first over the characters of the
+/// KEYSEQs of every map and printable ASCII, which a key gives as is where
+/// no rule matches. Inputs are cut short as soon as they commit text that
+/// `target` does not start with. If no action of the input method deletes
+/// text or moves the cursor, so that text is only ever added at the end,
+/// they are also cut short when the preedit stops matching `target`.
+pub fn keys_for(im_info: &ImInfo, target: &str, limits: &SearchLimits) -> KeysFor {
+    if target.is_empty() {
+        return KeysFor::Found(vec![String::new()]);
+    }
+
+    let keys: BTreeSet<char> = im_info
+        .maps
+        .values()
+        .flat_map(|map| map.keyseqs())
+        .flat_map(|(keyseq, _)| keyseq.chars().collect::<Vec<_>>())
+        .chain(' '..='~')
+        .collect();
+
+    let append_only = im_info
+        .maps
+        .values()
+        .flat_map(|map| &map.rules)
+        .chain(
+            im_info
+                .states
+                .iter()
+                .flat_map(|state| state.branches.iter().map(|branch| &branch.actions)),
+        )
+        .flat_map(|program| &program.ops)
+        .all(|op| !matches!(op, Op::Delete(_) | Op::Move(_)));
+
+    let start = Session::new(im_info);
+    let mut seen = BTreeSet::from([SessionKey::new(&start)]);
+    let mut level = vec![Reached {
+        session: start,
+        paths: vec![String::new()],
+        converts_to_target: false,
+    }];
+
+    for _ in 0..limits.max_keys {
+        let mut next: Vec<Reached> = Vec::new();
+        let mut index: BTreeMap<SessionKey, usize> = BTreeMap::new();
+        let mut limit_reached = false;
+
+        'level: for reached in &level {
+            for &key in &keys {
+                let mut session = reached.session.clone();
+                session.handle_key(im_info, key);
+                if !could_give(&session, target, append_only) {
+                    continue;
+                }
+
+                let session_key = SessionKey::new(&session);
+                if seen.contains(&session_key) {
+                    continue;
+                }
+                let paths = reached.paths.iter().map(|path| {
+                    let mut path = path.clone();
+                    path.push(key);
+                    path
+                });
+
+                if let Some(&idx) = index.get(&session_key) {
+                    let other = &mut next[idx];
+                    let room = limits.max_results.saturating_sub(other.paths.len());
+                    other.paths.extend(paths.take(room));
+                    continue;
+                }
+
+                if seen.len() + index.len() >= limits.max_states {
+                    limit_reached = true;
+                    break 'level;
+                }
+                let mut done = session.clone();
+                done.flush(im_info);
+                index.insert(session_key, next.len());
+                next.push(Reached {
+                    session,
+                    paths: paths.take(limits.max_results).collect(),
+                    converts_to_target: done.context().committed == target,
+                });
+            }
+        }
+
+        let found: Vec<String> = next
+            .iter()
+            .filter(|reached| reached.converts_to_target)
+            .flat_map(|reached| reached.paths.iter().cloned())
+            .take(limits.max_results)
+            .collect();
+        if !found.is_empty() {
+            return KeysFor::Found(found);
+        }
+        if limit_reached {
+            return KeysFor::LimitReached;
+        }
+        if next.is_empty() {
+            break;
+        }
+
+        seen.extend(index.into_keys());
+        level = next;
+    }
+
+    KeysFor::NotTypeable
+}
+
+/// Whether more keys could make `session` give `target`.
+fn could_give(session: &Session, target: &str, append_only: bool) -> bool {
+    let ctx = session.context();
+    let Some(rest) = target.strip_prefix(ctx.committed.as_str()) else {
+        return false;
+    };
+    !append_only || {
+        let mut rest = rest.chars();
+        ctx.preedit.iter().all(|&ch| rest.next() == Some(ch))
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Whether different inputs give the same text, and whether the characters
+//! of a script can be typed at all.
+
+use crate::bytecode::Op;
+use crate::char_names::CharNames;
+use crate::cheatsheet::code_points;
+use crate::context::convert;
+use crate::im_info::ImInfo;
+use crate::reverse::SessionKey;
+use crate::session::Session;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+/// Bounds on the inputs tried by [`round_trip`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundTripLimits {
+    /// Maximum number of keys in an input.
+    pub max_keys: usize,
+    /// Maximum number of inputs tried.
+    pub max_inputs: usize,
+}
+
+impl Default for RoundTripLimits {
+    fn default() -> Self {
+        RoundTripLimits {
+            max_keys: 3,
+            max_inputs: 1 << 18,
+        }
+    }
+}
+
+/// Inputs that all convert to the same text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    pub output: String,
+    /// Shortest first
+    pub inputs: Vec<String>,
+}
+
+/// A character of the block that no input tried gives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Untypeable {
+    pub ch: char,
+    /// Whether some rule or branch action inserts the character, so that
+    /// longer inputs than those tried may still give it
+    pub in_rules: bool,
+}
+
+/// The result of [`round_trip`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundTripReport {
+    /// Number of inputs tried
+    pub inputs: usize,
+    /// Whether [`RoundTripLimits::max_inputs`] stopped the search before
+    /// all inputs of [`RoundTripLimits::max_keys`] keys were tried
+    pub truncated: bool,
+    /// Ordered by the length of their shortest input, then by output
+    pub collisions: Vec<Collision>,
+    /// In code point order
+    pub untypeable: Vec<Untypeable>,
+}
+
+/// Type every input of up to [`RoundTripLimits::max_keys`] keys made of the
+/// characters of the KEYSEQs of `im_info`, and report inputs that
+/// [`convert`](crate::context::convert) to the same text, and the
+/// characters of `block` that none of them gives.
+///
+/// An input that leaves the input method just as a shorter or earlier input
+/// did is not typed further, since every key typed after it would give the
+/// same text as after the other; only the shortest such inputs are
+/// reported as colliding. Of the inputs giving the same text, those that
+/// differ from the shortest one only as shorter inputs do, as "hko" from
+/// "hk" where "ko" gives what "k" does, are left out too.
+///
+/// If `names` is not empty, code points of `block` it has no name for are
+/// taken to be unassigned and left out.
+pub fn round_trip(
+    im_info: &ImInfo,
+    block: RangeInclusive<char>,
+    names: &CharNames,
+    limits: &RoundTripLimits,
+) -> RoundTripReport {
+    let keys: BTreeSet<char> = im_info
+        .maps
+        .values()
+        .flat_map(|map| map.keyseqs())
+        .flat_map(|(keyseq, _)| keyseq.chars().collect::<Vec<_>>())
+        .collect();
+
+    let start = Session::new(im_info);
+    let mut seen = BTreeSet::from([SessionKey::new(&start)]);
+    let mut level = vec![(String::new(), start)];
+    let mut outputs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut inputs = 0;
+    let mut truncated = false;
+
+    'search: for _ in 0..limits.max_keys {
+        let mut next = Vec::new();
+        for (path, session) in &level {
+            for &key in &keys {
+                if inputs >= limits.max_inputs {
+                    truncated = true;
+                    break 'search;
+                }
+                inputs += 1;
+
+                let mut session = session.clone();
+                session.handle_key(im_info, key);
+                let mut path = path.clone();
+                path.push(key);
+
+                let mut done = session.clone();
+                done.flush(im_info);
+                outputs
+                    .entry(done.take_committed())
+                    .or_default()
+                    .push(path.clone());
+                if seen.insert(SessionKey::new(&session)) {
+                    next.push((path, session));
+                }
+            }
+        }
+        level = next;
+    }
+
+    let produced: BTreeSet<char> = outputs.keys().flat_map(|output| output.chars()).collect();
+    let mut collisions: Vec<Collision> = outputs
+        .into_iter()
+        .filter_map(|(output, mut inputs)| {
+            let shortest = inputs.first()?.clone();
+            inputs.retain(|input| *input == shortest || !explained(im_info, &shortest, input));
+            (inputs.len() > 1).then_some(Collision { output, inputs })
+        })
+        .collect();
+    collisions.sort_by_key(|collision| collision.inputs[0].chars().count());
+
+    let inserted = inserted_chars(im_info);
+    let untypeable = block
+        .filter(|ch| !produced.contains(ch))
+        .filter(|&ch| names.is_empty() || names.name(ch).is_some())
+        .map(|ch| Untypeable {
+            ch,
+            in_rules: inserted.contains(&ch),
+        })
+        .collect();
+
+    RoundTripReport {
+        inputs,
+        truncated,
+        collisions,
+        untypeable,
+    }
+}
+
+/// Whether inputs `a` and `b` only differ as shorter inputs do: both
+/// split in two so that each part of `a` gives the same text as that of `b`.
+fn explained(im_info: &ImInfo, a: &str, b: &str) -> bool {
+    let same = |x: &str, y: &str| convert(im_info, x) == convert(im_info, y);
+    let splits =
+        |text: &str| -> Vec<usize> { text.char_indices().skip(1).map(|(idx, _)| idx).collect() };
+    splits(a).into_iter().any(|i| {
+        splits(b)
+            .into_iter()
+            .any(|j| same(&a[..i], &b[..j]) && same(&a[i..], &b[j..]))
+    })
+}
+
+/// The characters that the actions of any rule or branch insert as text.
+fn inserted_chars(im_info: &ImInfo) -> BTreeSet<char> {
+    let programs = im_info.maps.values().flat_map(|map| &map.rules).chain(
+        im_info
+            .states
+            .iter()
+            .flat_map(|state| state.branches.iter().map(|branch| &branch.actions)),
+    );
+    let mut chars = BTreeSet::new();
+    for op in programs.flat_map(|program| &program.ops) {
+        match op {
+            Op::Insert(text) => chars.extend(text.chars()),
+            Op::InsertChar(ch) => {
+                chars.insert(*ch);
+            }
+            _ => {}
+        }
+    }
+    chars
+}
+
+/// The report as text: a table of collisions, then a table of untypeable
+/// characters.
+impl fmt::Display for RoundTripReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} inputs tried", self.inputs)?;
+        if self.truncated {
+            write!(f, ", stopped at the limit")?;
+        }
+        writeln!(f)?;
+
+        if !self.collisions.is_empty() {
+            writeln!(f)?;
+            let rows: Vec<[String; 3]> = self
+                .collisions
+                .iter()
+                .map(|collision| {
+                    let inputs: Vec<String> = collision
+                        .inputs
+                        .iter()
+                        .map(|input| format!("\"{}\"", input))
+                        .collect();
+                    [
+                        format!("\"{}\"", collision.output),
+                        code_points(&collision.output),
+                        inputs.join(", "),
+                    ]
+                })
+                .collect();
+            write_table(f, ["Output", "Code points", "Inputs"], &rows)?;
+        }
+
+        if !self.untypeable.is_empty() {
+            writeln!(f)?;
+            let rows: Vec<[String; 3]> = self
+                .untypeable
+                .iter()
+                .map(|untypeable| {
+                    let ch = untypeable.ch.to_string();
+                    let in_rules = if untypeable.in_rules { "yes" } else { "no" };
+                    [code_points(&ch), ch, in_rules.to_string()]
+                })
+                .collect();
+            write_table(f, ["Untypeable", "Character", "In rules"], &rows)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write `rows` under `header`, each column padded to its widest cell.
+fn write_table<const N: usize>(
+    f: &mut fmt::Formatter<'_>,
+    header: [&str; N],
+    rows: &[[String; N]],
+) -> fmt::Result {
+    let mut widths = header.map(|cell| cell.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = header.map(|cell| cell.to_string());
+    for row in core::iter::once(&header).chain(rows) {
+        let mut line = String::new();
+        for (idx, (cell, width)) in row.iter().zip(widths).enumerate() {
+            line.push_str(cell);
+            if idx + 1 < N {
+                line.extend(core::iter::repeat_n(' ', width - cell.chars().count() + 2));
+            }
+        }
+        writeln!(f, "{}", line)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+use crate::bytecode::*;
+use crate::completion::*;
+use crate::context::*;
+use crate::im_info::*;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// An input session that receives one key at a time, as from a keyboard.
+///
+/// Keys that may still become part of a longer KEYSEQ are held back until
+/// the next key (or [`Session::flush`]) decides the match, so the result is
+/// the same as converting the whole input at once.
+#[derive(Clone)]
+pub struct Session {
+    ctx: Context,
+    pending: Vec<char>,
+}
+
+impl Session {
+    pub fn new(im_info: &ImInfo) -> Self {
+        Session {
+            ctx: Context::new(im_info.initial_state()),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn handle_key(&mut self, im_info: &ImInfo, key: char) {
+        self.pending.push(key);
+
+        while !self.pending.is_empty() && !self.waits_for_more(im_info) {
+            self.consume_pending(im_info);
+        }
+    }
+
+    /// Resolve any held back keys and commit the preedit, as at the end of
+    /// input.
+    pub fn flush(&mut self, im_info: &ImInfo) {
+        while !self.pending.is_empty() {
+            self.consume_pending(im_info);
+        }
+        self.ctx.commit();
+    }
+
+    /// Take the text committed so far.
+    pub fn take_committed(&mut self) -> String {
+        core::mem::take(&mut self.ctx.committed)
+    }
+
+    /// The uncommitted text: the preedit followed by any held back keys.
+    pub fn preedit(&self) -> String {
+        self.ctx.preedit.iter().chain(&self.pending).collect()
+    }
+
+    pub(crate) fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Keys held back because they may still become part of a longer
+    /// KEYSEQ.
+    pub fn pending(&self) -> &[char] {
+        &self.pending
+    }
+
+    /// What can be typed after the held back keys, shortest first, as
+    /// [`completions`] lists it. With no keys held back, every KEYSEQ of
+    /// the current state is listed.
+    pub fn completions(&self, im_info: &ImInfo) -> Vec<Completion> {
+        completions(im_info, self.ctx.current_state, &self.pending)
+    }
+
+    pub fn current_state(&self) -> StateId {
+        self.ctx.current_state
+    }
+
+    pub fn current_state_name<'a>(&self, im_info: &'a ImInfo) -> &'a str {
+        im_info.state_name(self.ctx.current_state)
+    }
+
+    /// Title for the status indicator in the current state.
+    pub fn status_title<'a>(&self, im_info: &'a ImInfo) -> &'a Text {
+        im_info.state_title(self.ctx.current_state)
+    }
+
+    pub fn reset(&mut self, im_info: &ImInfo) {
+        *self = Session::new(im_info);
+    }
+
+    fn waits_for_more(&self, im_info: &ImInfo) -> bool {
+        let Some(state) = im_info.state_by_id(self.ctx.current_state) else {
+            return false;
+        };
+
+        state
+            .branches
+            .iter()
+            .filter_map(|branch| im_info.maps.get(&branch.map_name))
+            .any(|map| {
+                let mut walker = map.walker();
+                self.pending.iter().all(|&key| walker.step(key)) && walker.can_continue()
+            })
+    }
+
+    fn consume_pending(&mut self, im_info: &ImInfo) {
+        let consumed = self.ctx.step_or_commit(im_info, &self.pending).consumed();
+        self.pending.drain(..consumed);
+    }
+}
@@ -0,0 +1,595 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! A binary snapshot of a loaded input method.
+//!
+//! A snapshot holds everything [`load_im_info`](crate::im_info::load_im_info)
+//! builds from a .mim file: metadata, interned names, states with their
+//! compiled branch actions, and each map's compiled rules and FST. Loading it
+//! does no parsing or FST building, and the FSTs are used in place when the
+//! snapshot is static or shared. They are still read once as the snapshot
+//! is checked, so loading takes time in proportion to the snapshot's size,
+//! unless [`load_snapshot_static_trusted`] skips the check.
+//!
+//! All integers are little-endian. The layout is
+//!
+//! ```text
+//! magic "M17NMIM\0", version: u32
+//! lang, name: str; title, description: text; version: str
+//! variables, state_names: u32 count, str*
+//! maps: u32 count, (name: str, rules: u32 count, program*,
+//!                   invalid_keyseqs: u32 count, u32*, fst: u64 length, bytes)*
+//! states: u32 count, (id: u32, name: str, title: u8 flag [text],
+//!                     branches: u32 count, (map_name: str, program)*)*
+//! ```
+//!
+//! where a str is a u32 length and UTF-8 bytes, a text is a str and a u8
+//! translatable flag, and a program is a u32 count of ops, each a u8 tag and
+//! its operands.
+
+use crate::bytecode::*;
+use crate::im_info::*;
+use fst::Map;
+use fst::Streamer;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 8] = b"M17NMIM\0";
+
+/// Version of the snapshot format written by [`write_snapshot`]. Snapshots
+/// of other versions are rejected.
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotErrorKind {
+    /// The data does not start with the snapshot magic
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    Truncated,
+    InvalidUtf8,
+    /// An unknown tag, or an index out of range
+    InvalidValue,
+    InvalidFst,
+}
+
+/// An error loading a snapshot, with the byte offset it was found at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotError {
+    pub kind: SnapshotErrorKind,
+    pub offset: usize,
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            SnapshotErrorKind::NotASnapshot => write!(f, "not an input method snapshot"),
+            SnapshotErrorKind::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported snapshot version {} (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotErrorKind::Truncated => write!(f, "snapshot truncated at byte {}", self.offset),
+            SnapshotErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 at byte {}", self.offset),
+            SnapshotErrorKind::InvalidValue => write!(f, "invalid value at byte {}", self.offset),
+            SnapshotErrorKind::InvalidFst => write!(f, "invalid FST at byte {}", self.offset),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Serialize a loaded input method. Maps are written in name order, so the
+/// same input method always gives the same bytes.
+///
+/// Of states defined more than once under the same name, only the first is
+/// written, as it is the only one ever entered.
+pub fn write_snapshot(im_info: &ImInfo) -> Vec<u8> {
+    let mut out = Writer::default();
+    out.bytes(MAGIC);
+    out.u32(SNAPSHOT_VERSION);
+
+    out.str(&im_info.lang);
+    out.str(&im_info.name);
+    out.text(&im_info.title);
+    out.text(&im_info.description);
+    out.str(&im_info.version);
+    out.symbols(&im_info.variables);
+    out.symbols(&im_info.state_names);
+
+    out.u32(im_info.maps.len() as u32);
+    for (name, map) in &im_info.maps {
+        out.str(name);
+        out.u32(map.rules.len() as u32);
+        for rule in &map.rules {
+            out.program(rule);
+        }
+        out.u32(map.invalid_keyseqs.len() as u32);
+        for &rule in &map.invalid_keyseqs {
+            out.u32(rule);
+        }
+        let fst = map.fst.as_fst().as_bytes();
+        out.u64(fst.len() as u64);
+        out.bytes(fst);
+    }
+
+    let mut written = BTreeSet::new();
+    let states: Vec<&State> = im_info
+        .states
+        .iter()
+        .filter(|state| written.insert(state.id))
+        .collect();
+    out.u32(states.len() as u32);
+    for state in states {
+        out.u32(state.id);
+        out.str(&state.name);
+        match &state.title {
+            Some(title) => {
+                out.u8(1);
+                out.text(title);
+            }
+            None => out.u8(0),
+        }
+        out.u32(state.branches.len() as u32);
+        for branch in &state.branches {
+            out.str(&branch.map_name);
+            out.program(&branch.actions);
+        }
+    }
+
+    out.buf
+}
+
+/// Load a snapshot, copying the FSTs out of `bytes`.
+pub fn load_snapshot(bytes: &[u8]) -> Result<ImInfo, SnapshotError> {
+    read_snapshot(bytes, true, |range| FstData::Owned(bytes[range].to_vec()))
+}
+
+/// Load a snapshot embedded in the program, using its FSTs in place.
+pub fn load_snapshot_static(bytes: &'static [u8]) -> Result<ImInfo, SnapshotError> {
+    read_snapshot(bytes, true, |range| FstData::Static(&bytes[range]))
+}
+
+/// Like [`load_snapshot_static`], but without reading the FSTs through to
+/// check them and the rule indices they hold, so that loading does not take
+/// time in proportion to their size. Only for bytes [`write_snapshot`]
+/// wrote in a build with the same [`SNAPSHOT_VERSION`], such as those
+/// `include_mim!` embeds: a corrupt FST may load and panic when used.
+pub fn load_snapshot_static_trusted(bytes: &'static [u8]) -> Result<ImInfo, SnapshotError> {
+    read_snapshot(bytes, false, |range| FstData::Static(&bytes[range]))
+}
+
+/// Load a snapshot from a shared buffer, using its FSTs in place. The
+/// buffer is kept alive by the returned [`ImInfo`].
+pub fn load_snapshot_shared(
+    buffer: Arc<dyn AsRef<[u8]> + Send + Sync>,
+) -> Result<ImInfo, SnapshotError> {
+    let bytes = (*buffer).as_ref();
+    read_snapshot(bytes, true, |range| FstData::Shared {
+        buffer: Arc::clone(&buffer),
+        range,
+    })
+}
+
+/// Map a snapshot file into memory and load it, using its FSTs in place.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated while the returned
+/// [`ImInfo`] is alive.
+#[cfg(not(target_arch = "wasm32"))]
+pub unsafe fn load_snapshot_file(path: impl AsRef<std::path::Path>) -> std::io::Result<ImInfo> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: the caller guarantees the file is not changed while mapped
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    load_snapshot_shared(Arc::new(mmap))
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+/// Read a snapshot, with the FSTs read through and checked if `verify`.
+fn read_snapshot(
+    bytes: &[u8],
+    verify: bool,
+    mut fst_data: impl FnMut(std::ops::Range<usize>) -> FstData,
+) -> Result<ImInfo, SnapshotError> {
+    let mut input = Reader {
+        bytes,
+        pos: 0,
+        variables: 0,
+        states: 0,
+    };
+
+    if bytes.get(..MAGIC.len()) != Some(MAGIC) {
+        return Err(input.error(SnapshotErrorKind::NotASnapshot));
+    }
+    input.pos = MAGIC.len();
+    let version = input.u32()?;
+    if version != SNAPSHOT_VERSION {
+        return Err(input.error(SnapshotErrorKind::UnsupportedVersion(version)));
+    }
+
+    let mut im_info = ImInfo {
+        lang: input.str()?,
+        name: input.str()?,
+        title: input.text()?,
+        description: input.text()?,
+        version: input.str()?,
+        ..Default::default()
+    };
+    im_info.variables = input.symbols()?;
+    im_info.state_names = input.symbols()?;
+    input.variables = im_info.variables.len();
+    input.states = im_info.state_names.len();
+
+    let mut maps = BTreeMap::new();
+    for _ in 0..input.u32()? {
+        let name = input.str()?;
+        let mut rules = Vec::new();
+        for _ in 0..input.u32()? {
+            rules.push(input.program()?);
+        }
+        let mut invalid_keyseqs = Vec::new();
+        for _ in 0..input.u32()? {
+            let offset = input.pos;
+            let rule = input.u32()?;
+            if rule as usize >= rules.len() {
+                return Err(input.error_at(SnapshotErrorKind::InvalidValue, offset));
+            }
+            invalid_keyseqs.push(rule);
+        }
+        let offset = input.pos;
+        let len = input.u64()?;
+        let range = input.take(len)?;
+        let fst = Map::new(fst_data(range))
+            .map_err(|_| input.error_at(SnapshotErrorKind::InvalidFst, offset))?;
+        if verify {
+            fst.as_fst()
+                .verify()
+                .map_err(|_| input.error_at(SnapshotErrorKind::InvalidFst, offset))?;
+            // Every KEYSEQ must lead to one of the map's rules
+            let mut keyseqs = fst.stream();
+            while let Some((_, rule)) = keyseqs.next() {
+                if rule >= rules.len() as u64 {
+                    return Err(input.error_at(SnapshotErrorKind::InvalidFst, offset));
+                }
+            }
+        }
+        maps.insert(
+            name,
+            KeySeqRuleMap {
+                fst,
+                rules,
+                invalid_keyseqs,
+            },
+        );
+    }
+    im_info.maps = maps;
+
+    let mut ids = BTreeSet::new();
+    for _ in 0..input.u32()? {
+        let offset = input.pos;
+        let id = input.state_id()?;
+        let name = input.str()?;
+        // Each state is stored once, under the id of its name
+        if im_info.state_names.name(id) != Some(name.as_str()) || !ids.insert(id) {
+            return Err(input.error_at(SnapshotErrorKind::InvalidValue, offset));
+        }
+        let title = match input.u8()? {
+            0 => None,
+            1 => Some(input.text()?),
+            _ => return Err(input.invalid()),
+        };
+        let mut branches = Vec::new();
+        for _ in 0..input.u32()? {
+            branches.push(Branch {
+                map_name: input.str()?,
+                actions: input.program()?,
+            });
+        }
+        im_info.states.push(State {
+            id,
+            name,
+            title,
+            branches,
+        });
+    }
+
+    if input.pos != bytes.len() {
+        return Err(input.invalid());
+    }
+    Ok(im_info)
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u32(s.len() as u32);
+        self.bytes(s.as_bytes());
+    }
+
+    fn text(&mut self, text: &Text) {
+        self.str(&text.text);
+        self.u8(text.translatable as u8);
+    }
+
+    fn symbols(&mut self, symbols: &Symbols) {
+        self.u32(symbols.len() as u32);
+        for (_, name) in symbols.iter() {
+            self.str(name);
+        }
+    }
+
+    fn position(&mut self, pos: Position) {
+        match pos {
+            Position::Start => self.u8(0),
+            Position::End => self.u8(1),
+            Position::Prev => self.u8(2),
+            Position::Next => self.u8(3),
+            Position::Relative(offset) => {
+                self.u8(4);
+                self.u32(offset as u32);
+            }
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        self.u32(program.ops.len() as u32);
+        for op in &program.ops {
+            match op {
+                Op::Insert(s) => {
+                    self.u8(0);
+                    self.str(s);
+                }
+                Op::InsertChar(ch) => {
+                    self.u8(1);
+                    self.u32(*ch as u32);
+                }
+                Op::InsertVar(var) => {
+                    self.u8(2);
+                    self.u32(*var);
+                }
+                Op::Delete(pos) => {
+                    self.u8(3);
+                    self.position(*pos);
+                }
+                Op::Move(pos) => {
+                    self.u8(4);
+                    self.position(*pos);
+                }
+                Op::Set(var) => {
+                    self.u8(5);
+                    self.u32(*var);
+                }
+                Op::Shift(state) => {
+                    self.u8(6);
+                    self.u32(*state);
+                }
+                Op::Commit => self.u8(7),
+                Op::PushInt(i) => {
+                    self.u8(8);
+                    self.u64(*i as u64);
+                }
+                Op::PushVar(var) => {
+                    self.u8(9);
+                    self.u32(*var);
+                }
+                Op::PushSurrounding(offset) => {
+                    self.u8(10);
+                    self.u32(*offset as u32);
+                }
+                Op::Binary(op) => {
+                    self.u8(11);
+                    self.u8(BINARY_OPS.iter().position(|o| o == op).unwrap() as u8);
+                }
+                Op::Not => self.u8(12),
+                Op::JumpIfZero(target) => {
+                    self.u8(13);
+                    self.u32(*target);
+                }
+                Op::Jump(target) => {
+                    self.u8(14);
+                    self.u32(*target);
+                }
+                Op::Unknown(name) => {
+                    self.u8(15);
+                    self.str(name);
+                }
+                Op::Pushback(keys) => {
+                    self.u8(16);
+                    self.u32(*keys);
+                }
+            }
+        }
+    }
+}
+
+const BINARY_OPS: [BinaryOp; 11] = [
+    BinaryOp::Add,
+    BinaryOp::Sub,
+    BinaryOp::Mul,
+    BinaryOp::Div,
+    BinaryOp::And,
+    BinaryOp::Or,
+    BinaryOp::Eq,
+    BinaryOp::Lt,
+    BinaryOp::Gt,
+    BinaryOp::Le,
+    BinaryOp::Ge,
+];
+
+/// Reads a snapshot, checking every index so that a corrupt snapshot is
+/// rejected rather than loaded. The FSTs and the rule indices they hold are
+/// checked by [`read_snapshot`].
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Number of variables, which variable operands must be below
+    variables: usize,
+    /// Number of state names, which state operands must be below
+    states: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, kind: SnapshotErrorKind) -> SnapshotError {
+        self.error_at(kind, self.pos)
+    }
+
+    fn error_at(&self, kind: SnapshotErrorKind, offset: usize) -> SnapshotError {
+        SnapshotError { kind, offset }
+    }
+
+    fn invalid(&self) -> SnapshotError {
+        self.error(SnapshotErrorKind::InvalidValue)
+    }
+
+    /// Skip `len` bytes, returning their range.
+    fn take(&mut self, len: u64) -> Result<std::ops::Range<usize>, SnapshotError> {
+        let start = self.pos;
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| start.checked_add(len))
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| self.error(SnapshotErrorKind::Truncated))?;
+        self.pos = end;
+        Ok(start..end)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let range = self.take(N as u64)?;
+        Ok(self.bytes[range].try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn str(&mut self) -> Result<String, SnapshotError> {
+        let len = self.u32()?;
+        let start = self.pos;
+        let range = self.take(len as u64)?;
+        match std::str::from_utf8(&self.bytes[range]) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => Err(self.error_at(SnapshotErrorKind::InvalidUtf8, start)),
+        }
+    }
+
+    fn text(&mut self) -> Result<Text, SnapshotError> {
+        let text = self.str()?;
+        let translatable = match self.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(self.invalid()),
+        };
+        Ok(Text { text, translatable })
+    }
+
+    fn symbols(&mut self) -> Result<Symbols, SnapshotError> {
+        let mut symbols = Symbols::default();
+        for i in 0..self.u32()? {
+            let name = self.str()?;
+            // Names are unique, so each is interned as the next id
+            if symbols.intern(&name) != i {
+                return Err(self.invalid());
+            }
+        }
+        Ok(symbols)
+    }
+
+    fn var_id(&mut self) -> Result<VarId, SnapshotError> {
+        let var = self.u32()?;
+        if var as usize >= self.variables {
+            return Err(self.invalid());
+        }
+        Ok(var)
+    }
+
+    fn state_id(&mut self) -> Result<StateId, SnapshotError> {
+        let state = self.u32()?;
+        if state as usize >= self.states {
+            return Err(self.invalid());
+        }
+        Ok(state)
+    }
+
+    fn position(&mut self) -> Result<Position, SnapshotError> {
+        Ok(match self.u8()? {
+            0 => Position::Start,
+            1 => Position::End,
+            2 => Position::Prev,
+            3 => Position::Next,
+            4 => Position::Relative(self.u32()? as i32),
+            _ => return Err(self.invalid()),
+        })
+    }
+
+    fn program(&mut self) -> Result<Program, SnapshotError> {
+        let count = self.u32()?;
+        let mut ops = Vec::new();
+        for i in 0..count {
+            let op = match self.u8()? {
+                0 => Op::Insert(self.str()?.into()),
+                1 => Op::InsertChar(char::from_u32(self.u32()?).ok_or_else(|| self.invalid())?),
+                2 => Op::InsertVar(self.var_id()?),
+                3 => Op::Delete(self.position()?),
+                4 => Op::Move(self.position()?),
+                5 => Op::Set(self.var_id()?),
+                6 => Op::Shift(self.state_id()?),
+                7 => Op::Commit,
+                8 => Op::PushInt(self.u64()? as i64),
+                9 => Op::PushVar(self.var_id()?),
+                10 => Op::PushSurrounding(self.u32()? as i32),
+                11 => Op::Binary(
+                    *BINARY_OPS
+                        .get(self.u8()? as usize)
+                        .ok_or_else(|| self.invalid())?,
+                ),
+                12 => Op::Not,
+                // Jumps only go forward, so every program terminates
+                13 => Op::JumpIfZero(self.jump_target(i, count)?),
+                14 => Op::Jump(self.jump_target(i, count)?),
+                15 => Op::Unknown(self.str()?.into()),
+                16 => Op::Pushback(self.u32()?),
+                _ => return Err(self.invalid()),
+            };
+            ops.push(op);
+        }
+        Ok(Program { ops })
+    }
+
+    fn jump_target(&mut self, index: u32, count: u32) -> Result<u32, SnapshotError> {
+        let target = self.u32()?;
+        if target <= index || target > count {
+            return Err(self.invalid());
+        }
+        Ok(target)
+    }
+}
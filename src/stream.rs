@@ -0,0 +1,135 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Converting text as it streams by.
+
+use crate::im_info::ImInfo;
+use crate::session::Session;
+use std::io::{self, Read, Write};
+
+/// A writer that converts the UTF-8 text written to it and writes the result
+/// to another writer.
+///
+/// Text is fed to the input method one character at a time, holding back
+/// only the keys that may still become part of a longer KEYSEQ, and committed
+/// text is passed on as soon as it is final. Uncommitted text stays in the
+/// preedit until the input method commits it or [`StreamConverter::finish`]
+/// is called at the end of input. [`Write::flush`] flushes the inner writer
+/// but does not end the input.
+pub struct StreamConverter<'a, W: Write> {
+    im_info: &'a ImInfo,
+    session: Session,
+    writer: W,
+    /// The start of a UTF-8 sequence split between writes
+    partial: Vec<u8>,
+}
+
+impl<'a, W: Write> StreamConverter<'a, W> {
+    pub fn new(im_info: &'a ImInfo, writer: W) -> Self {
+        StreamConverter {
+            im_info,
+            session: Session::new(im_info),
+            writer,
+            partial: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// End the input: resolve held back keys, write the rest of the text and
+    /// return the inner writer. Fails if the input ended inside a UTF-8
+    /// sequence.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.partial.is_empty() {
+            return Err(invalid_utf8());
+        }
+        self.session.flush(self.im_info);
+        self.write_committed()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn feed(&mut self, text: &str) {
+        for key in text.chars() {
+            self.session.handle_key(self.im_info, key);
+        }
+    }
+
+    fn write_committed(&mut self) -> io::Result<()> {
+        let committed = self.session.take_committed();
+        self.writer.write_all(committed.as_bytes())
+    }
+}
+
+impl<W: Write> Write for StreamConverter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = buf;
+
+        if let Some(&first) = self.partial.first() {
+            let len = utf8_len(first);
+            let take = (len - self.partial.len()).min(bytes.len());
+            self.partial.extend_from_slice(&bytes[..take]);
+            bytes = &bytes[take..];
+            if self.partial.len() < len {
+                return Ok(buf.len());
+            }
+
+            let partial = std::mem::take(&mut self.partial);
+            let text = std::str::from_utf8(&partial).map_err(|_| invalid_utf8())?;
+            self.feed(text);
+        }
+
+        match std::str::from_utf8(bytes) {
+            Ok(text) => self.feed(text),
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                // The bytes up to `valid_up_to` are valid UTF-8
+                self.feed(std::str::from_utf8(valid).unwrap());
+                if err.error_len().is_some() {
+                    self.write_committed()?;
+                    return Err(invalid_utf8());
+                }
+                // The input ends inside a sequence
+                self.partial.extend_from_slice(rest);
+            }
+        }
+
+        self.write_committed()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Convert all UTF-8 text from `reader` into `writer`, returning the writer.
+pub fn convert_stream<R: Read, W: Write>(
+    im_info: &ImInfo,
+    mut reader: R,
+    writer: W,
+) -> io::Result<W> {
+    let mut converter = StreamConverter::new(im_info, writer);
+    io::copy(&mut reader, &mut converter)?;
+    converter.finish()
+}
+
+/// Length of the UTF-8 sequence starting with `first`, which is not ASCII
+/// and not a continuation byte.
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        _ => 2,
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
@@ -0,0 +1,455 @@
+// Copyright (C) 2025 Mahmud Nabil
+// Licensed under the GNU Lesser General Public License v2.1 or later.
+// See the LICENSE file for full terms.
+
+//! Step-by-step record of a conversion, for finding out why an input method
+//! produces what it does.
+
+use crate::bytecode::*;
+pub use crate::context::ActionSource;
+use crate::context::{Context, Step, TracedAction};
+use crate::im_info::ImInfo;
+use crate::json;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceAction {
+    pub source: ActionSource,
+    /// The action as it would be written in a .mim file, such as
+    /// `insert "ক"` or `shift init`. A `set` action is followed by the
+    /// value it assigned, as in `set X = 98`.
+    pub action: String,
+    pub preedit_before: String,
+    pub cursor_before: usize,
+    pub preedit_after: String,
+    pub cursor_after: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarChange {
+    pub name: String,
+    pub before: i64,
+    pub after: i64,
+}
+
+/// One match of a KEYSEQ, or one key that matched nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// The keys consumed, as a character range of the input
+    pub input: Range<usize>,
+    /// The keys handled: the KEYSEQ matched, which is longer than `input`
+    /// if keys were pushed back, or the key that matched nothing
+    pub keys: String,
+    pub state: String,
+    /// Map name, rule index in the map and branch index in the state, or
+    /// `None` if no rule matched
+    pub matched: Option<(String, usize, usize)>,
+    /// Index of the `nil` branch run because no rule matched
+    pub nil_branch: Option<usize>,
+    pub actions: Vec<TraceAction>,
+    pub variables: Vec<VarChange>,
+    /// The state after the step
+    pub next_state: String,
+}
+
+/// The result of [`trace_conversion`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub input: String,
+    pub output: String,
+    pub steps: Vec<TraceStep>,
+}
+
+/// Convert `input` as [`convert`](crate::context::convert) does, recording
+/// every step.
+pub fn trace_conversion(im_info: &ImInfo, input: &str) -> Trace {
+    let mut ctx = Context::new(im_info.initial_state());
+    let keys: Vec<char> = input.chars().collect();
+    let mut steps = Vec::new();
+    let mut i = 0;
+
+    while i < keys.len() {
+        let state = im_info.state_name(ctx.current_state).to_string();
+        let variables_before = ctx.variables.clone();
+        ctx.trace = Some(Vec::new());
+        let step = ctx.step_or_commit(im_info, &keys[i..]);
+        let actions = ctx
+            .trace
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|action| trace_action(im_info, action))
+            .collect();
+
+        let len = step.consumed();
+        let (handled, matched, nil_branch) = match step {
+            Step::Matched { found, .. } => (
+                found.len,
+                Some((found.map_name.to_string(), found.rule, found.branch)),
+                None,
+            ),
+            Step::Nil { branch, .. } => (1, None, Some(branch)),
+            Step::Unmatched => (1, None, None),
+        };
+
+        steps.push(TraceStep {
+            input: i..i + len,
+            keys: keys[i..i + handled].iter().collect(),
+            state,
+            matched,
+            nil_branch,
+            actions,
+            variables: var_changes(im_info, &variables_before, &ctx.variables),
+            next_state: im_info.state_name(ctx.current_state).to_string(),
+        });
+        i += len;
+    }
+
+    ctx.commit();
+    Trace {
+        input: input.to_string(),
+        output: ctx.committed,
+        steps,
+    }
+}
+
+fn trace_action(im_info: &ImInfo, action: TracedAction) -> TraceAction {
+    TraceAction {
+        source: action.source,
+        action: match action.value {
+            Some(value) => format!("{} = {}", describe(im_info, &action.op), value),
+            None => describe(im_info, &action.op),
+        },
+        preedit_before: action.preedit_before,
+        cursor_before: action.cursor_before,
+        preedit_after: action.preedit_after,
+        cursor_after: action.cursor_after,
+    }
+}
+
+fn var_changes(im_info: &ImInfo, before: &[i64], after: &[i64]) -> Vec<VarChange> {
+    (0..before.len().max(after.len()))
+        .filter_map(|idx| {
+            let old = before.get(idx).copied().unwrap_or(0);
+            let new = after.get(idx).copied().unwrap_or(0);
+            (old != new).then(|| VarChange {
+                name: var_name(im_info, idx as VarId).to_string(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+fn var_name(im_info: &ImInfo, var: VarId) -> &str {
+    im_info.variables.name(var).unwrap_or_default()
+}
+
+/// Write an action the way it appears in a .mim file.
+pub(crate) fn describe(im_info: &ImInfo, op: &Op) -> String {
+    match op {
+        Op::Insert(s) => format!("insert \"{}\"", s),
+        Op::InsertChar(ch) => format!("insert \"{}\"", ch),
+        Op::InsertVar(var) => format!("insert {}", var_name(im_info, *var)),
+        Op::Delete(pos) => format!("delete {}", position(*pos)),
+        Op::Move(pos) => format!("move {}", position(*pos)),
+        Op::Set(var) => format!("set {}", var_name(im_info, *var)),
+        Op::Shift(state) => format!("shift {}", im_info.state_name(*state)),
+        Op::Commit => "commit".to_string(),
+        Op::Pushback(keys) => format!("pushback {}", keys),
+        Op::Unknown(name) => format!("{} (unknown)", name),
+        op => format!("{:?}", op),
+    }
+}
+
+/// Write the actions of `program` the way they appear in a .mim file. The
+/// expressions of `set` and `cond` are rebuilt from the stack operations
+/// they compile to, and the actions of a `cond` arm are written in
+/// parentheses after its test.
+pub(crate) fn describe_program(im_info: &ImInfo, program: &Program) -> Vec<String> {
+    let mut actions = Vec::new();
+    describe_ops(im_info, &program.ops, 0..program.ops.len(), &mut actions);
+    actions
+}
+
+fn describe_ops(im_info: &ImInfo, ops: &[Op], range: Range<usize>, actions: &mut Vec<String>) {
+    let mut stack = Vec::new();
+    let mut pc = range.start;
+    while pc < range.end {
+        let op = &ops[pc];
+        pc += 1;
+        if push_expr(im_info, op, &mut stack) {
+            continue;
+        }
+        match op {
+            Op::Set(var) => actions.push(format!(
+                "set {} {}",
+                var_name(im_info, *var),
+                stack.pop().unwrap_or_default()
+            )),
+            Op::JumpIfZero(_) => {
+                let test = stack.pop().unwrap_or_default();
+                let (cond, end) = describe_cond(im_info, ops, pc - 1, test, range.end);
+                actions.push(cond);
+                pc = end;
+            }
+            op => actions.push(describe(im_info, op)),
+        }
+    }
+}
+
+/// Write the `cond` whose first arm is tested by the [`Op::JumpIfZero`] at
+/// `test_at`, with the expression `test`. Also give where the `cond` ends.
+fn describe_cond(
+    im_info: &ImInfo,
+    ops: &[Op],
+    mut test_at: usize,
+    mut test: String,
+    limit: usize,
+) -> (String, usize) {
+    let mut cond = String::from("cond");
+    loop {
+        // The arm ends with a jump to the end of the `cond`, and its test
+        // jumps past that when it fails
+        let next = match ops[test_at] {
+            Op::JumpIfZero(next) => (next as usize).clamp(test_at + 1, limit),
+            _ => return (cond, test_at),
+        };
+        let (arm_end, end) = match ops.get(next - 1) {
+            Some(Op::Jump(end)) if next - 1 > test_at => (next - 1, (*end as usize).min(limit)),
+            _ => (next, next),
+        };
+
+        let mut actions = Vec::new();
+        describe_ops(im_info, ops, test_at + 1..arm_end, &mut actions);
+        cond.push_str(" (");
+        cond.push_str(&test);
+        for action in actions {
+            cond.push_str(" (");
+            cond.push_str(&action);
+            cond.push(')');
+        }
+        cond.push(')');
+
+        // The test of the next arm, if any
+        let mut stack = Vec::new();
+        let mut at = next;
+        while at < end && push_expr(im_info, &ops[at], &mut stack) {
+            at += 1;
+        }
+        if at >= end || !matches!(ops[at], Op::JumpIfZero(_)) {
+            return (cond, end.max(next));
+        }
+        test = stack.pop().unwrap_or_default();
+        test_at = at;
+    }
+}
+
+/// If `op` works on the value stack, apply it to `stack` as text and return
+/// true.
+fn push_expr(im_info: &ImInfo, op: &Op, stack: &mut Vec<String>) -> bool {
+    match op {
+        Op::PushInt(value) => stack.push(value.to_string()),
+        Op::PushVar(var) => stack.push(var_name(im_info, *var).to_string()),
+        Op::PushSurrounding(offset) => stack.push(format!("@{:+}", offset)),
+        Op::Not => {
+            let value = stack.pop().unwrap_or_default();
+            stack.push(format!("(! {})", value));
+        }
+        Op::Binary(op) => {
+            let rhs = stack.pop().unwrap_or_default();
+            let lhs = stack.pop().unwrap_or_default();
+            let name = binary_name(*op);
+            let relation = matches!(
+                op,
+                BinaryOp::Eq | BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge
+            );
+            // Arithmetic operators over more than two arguments compile to
+            // one operation per argument after the first
+            let prefix = format!("({} ", name);
+            match lhs.strip_suffix(')') {
+                Some(args) if !relation && lhs.starts_with(&prefix) => {
+                    stack.push(format!("{} {})", args, rhs))
+                }
+                _ => stack.push(format!("{}{} {})", prefix, lhs, rhs)),
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn binary_name(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::And => "&",
+        BinaryOp::Or => "|",
+        BinaryOp::Eq => "=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Gt => ">",
+        BinaryOp::Le => "<=",
+        BinaryOp::Ge => ">=",
+    }
+}
+
+fn position(pos: Position) -> String {
+    match pos {
+        Position::Start => "@<".to_string(),
+        Position::End => "@>".to_string(),
+        Position::Prev => "@-".to_string(),
+        Position::Next => "@+".to_string(),
+        Position::Relative(offset) => offset.to_string(),
+    }
+}
+
+/// `text` with a `|` at character position `cursor`, quoted.
+fn with_cursor(text: &str, cursor: usize) -> String {
+    let mut out = String::from("\"");
+    for (i, ch) in text.chars().enumerate() {
+        if i == cursor {
+            out.push('|');
+        }
+        out.push(ch);
+    }
+    if cursor >= text.chars().count() {
+        out.push('|');
+    }
+    out.push('"');
+    out
+}
+
+impl Trace {
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"input\":");
+        json::push_str(&mut out, &self.input);
+        out.push_str(",\"output\":");
+        json::push_str(&mut out, &self.output);
+        out.push_str(",\"steps\":[");
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(
+                out,
+                "{{\"input\":[{},{}],\"keys\":",
+                step.input.start, step.input.end
+            );
+            json::push_str(&mut out, &step.keys);
+            out.push_str(",\"state\":");
+            json::push_str(&mut out, &step.state);
+            match &step.matched {
+                Some((map_name, rule, branch)) => {
+                    out.push_str(",\"map\":");
+                    json::push_str(&mut out, map_name);
+                    let _ = write!(out, ",\"rule\":{},\"branch\":{}", rule, branch);
+                }
+                None => out.push_str(",\"map\":null,\"rule\":null,\"branch\":null"),
+            }
+            match step.nil_branch {
+                Some(branch) => {
+                    let _ = write!(out, ",\"nil_branch\":{}", branch);
+                }
+                None => out.push_str(",\"nil_branch\":null"),
+            }
+            out.push_str(",\"actions\":[");
+            for (j, action) in step.actions.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                let _ = write!(
+                    out,
+                    "{{\"source\":\"{}\",\"action\":",
+                    action.source.as_str()
+                );
+                json::push_str(&mut out, &action.action);
+                out.push_str(",\"preedit_before\":");
+                json::push_str(&mut out, &action.preedit_before);
+                let _ = write!(
+                    out,
+                    ",\"cursor_before\":{},\"preedit_after\":",
+                    action.cursor_before
+                );
+                json::push_str(&mut out, &action.preedit_after);
+                let _ = write!(out, ",\"cursor_after\":{}}}", action.cursor_after);
+            }
+            out.push_str("],\"variables\":[");
+            for (j, change) in step.variables.iter().enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                out.push_str("{\"name\":");
+                json::push_str(&mut out, &change.name);
+                let _ = write!(
+                    out,
+                    ",\"before\":{},\"after\":{}}}",
+                    change.before, change.after
+                );
+            }
+            out.push_str("],\"next_state\":");
+            json::push_str(&mut out, &step.next_state);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// The trace as a text log, one line per step and per action. The cursor
+/// is shown as `|` in the preedit.
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            write!(
+                f,
+                "[{}..{}] \"{}\" in {}: ",
+                step.input.start, step.input.end, step.keys, step.state
+            )?;
+            let pushed_back = step.keys.chars().count() - step.input.len();
+            match (&step.matched, step.nil_branch) {
+                (Some((map_name, rule, branch)), _) if pushed_back > 0 => writeln!(
+                    f,
+                    "{} rule {} (branch {}), {} pushed back",
+                    map_name, rule, branch, pushed_back
+                )?,
+                (Some((map_name, rule, branch)), _) => {
+                    writeln!(f, "{} rule {} (branch {})", map_name, rule, branch)?
+                }
+                (None, Some(branch)) if step.input.is_empty() => {
+                    writeln!(f, "no match, nil branch {}", branch)?
+                }
+                (None, Some(branch)) => {
+                    writeln!(f, "no match, nil branch {}, committed as is", branch)?
+                }
+                (None, None) => writeln!(f, "no match, committed as is")?,
+            }
+            for action in &step.actions {
+                writeln!(
+                    f,
+                    "  {:<6} {:<24} {} -> {}",
+                    action.source.as_str(),
+                    action.action,
+                    with_cursor(&action.preedit_before, action.cursor_before),
+                    with_cursor(&action.preedit_after, action.cursor_after)
+                )?;
+            }
+            for change in &step.variables {
+                writeln!(
+                    f,
+                    "  {} = {} (was {})",
+                    change.name, change.after, change.before
+                )?;
+            }
+            if step.next_state != step.state {
+                writeln!(f, "  now in {}", step.next_state)?;
+            }
+        }
+        writeln!(f, "output: \"{}\"", self.output)
+    }
+}
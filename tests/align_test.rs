@@ -0,0 +1,117 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::align::*;
+use std::fs;
+
+fn load_sample() -> M17nMim {
+    M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap())
+}
+
+/// A segment as (input, output, map name and rule index).
+type Span<'a> = (String, String, Option<(&'a str, usize)>);
+
+fn spans<'a>(input: &str, conversion: &'a Conversion) -> Vec<Span<'a>> {
+    let slice = |text: &str, range: &std::ops::Range<usize>| -> String {
+        text.chars().skip(range.start).take(range.len()).collect()
+    };
+    conversion
+        .segments
+        .iter()
+        .map(|segment| {
+            let rule = segment.rule.as_ref().map(|r| (r.map_name.as_str(), r.rule));
+            (
+                slice(input, &segment.input),
+                slice(&conversion.output, &segment.output),
+                rule,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_aligned_segments() {
+    let mim = load_sample();
+    let input = "kakhi gim.";
+    let conversion = convert_aligned(mim.im_info(), input);
+    assert_eq!(conversion.output, mim.convert(input));
+
+    let expected = [
+        ("k", "ক", Some(("consonant", 0))),
+        ("a", "া", Some(("sign", 0))),
+        ("kh", "খ", Some(("consonant", 1))),
+        ("i", "ি", Some(("sign", 1))),
+        (" ", " ", None),
+        ("g", "গ", Some(("consonant", 2))),
+        ("i", "ি", Some(("sign", 1))),
+        ("m", "ম", Some(("consonant", 3))),
+        (".", "।", Some(("punct", 0))),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(i, o, r)| (i.to_string(), o.to_string(), *r))
+        .collect();
+    assert_eq!(spans(input, &conversion), expected);
+    assert_eq!(conversion.output_range_at(3), Some(2..3));
+    assert_eq!(conversion.output_range_at(10), None);
+}
+
+#[test]
+fn test_rewritten_output_moves_to_later_step() {
+    let mim = M17nMim::new(
+        r#"(input-method t rewrite)
+(map (m ("a" "a") ("b" (delete @-) "B")))
+(state (init (m)))"#,
+    );
+    let conversion = convert_aligned(mim.im_info(), "aab");
+    assert_eq!(conversion.output, "aB");
+    assert_eq!(
+        spans("aab", &conversion),
+        [
+            ("a".into(), "a".into(), Some(("m", 0))),
+            ("a".into(), "".into(), Some(("m", 0))),
+            ("b".into(), "B".into(), Some(("m", 1))),
+        ]
+    );
+    assert_eq!(conversion.segments[1].output, 1..1);
+}
+
+#[test]
+fn test_aligned_json() {
+    let mim = load_sample();
+    assert_eq!(
+        mim.convert_aligned("k\""),
+        concat!(
+            r#"{"output":"ক\"","segments":["#,
+            r#"{"input":[0,1],"output":[0,1],"map":"consonant","rule":0},"#,
+            r#"{"input":[1,2],"output":[1,2],"map":null,"rule":null}]}"#
+        )
+    );
+}
+
+#[test]
+fn test_utf16_ranges() {
+    let mim = M17nMim::new(
+        r#"(input-method t astral)
+(map (m ("a" "𝔸")))
+(state (init (m)))"#,
+    );
+    let conversion = convert_aligned(mim.im_info(), "a𝔸b");
+    let ranges = |conversion: &Conversion| -> Vec<_> {
+        conversion
+            .segments
+            .iter()
+            .map(|segment| (segment.input.clone(), segment.output.clone()))
+            .collect()
+    };
+    assert_eq!(
+        ranges(&conversion),
+        [(0..1, 0..1), (1..2, 1..2), (2..3, 2..3)]
+    );
+
+    let utf16 = conversion.in_utf16("a𝔸b");
+    assert_eq!(utf16.output, "𝔸𝔸b");
+    assert_eq!(ranges(&utf16), [(0..1, 0..2), (1..3, 2..4), (3..4, 4..5)]);
+    assert!(
+        mim.convert_aligned("a𝔸b")
+            .contains(r#""input":[1,3],"output":[2,4]"#)
+    );
+}
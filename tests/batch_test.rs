@@ -0,0 +1,104 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::batch::*;
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+
+fn load_sample() -> M17nMim {
+    M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap())
+}
+
+#[test]
+fn test_shared_between_threads() {
+    let mim = Arc::new(load_sample());
+
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let mim = Arc::clone(&mim);
+            thread::spawn(move || mim.convert("kakhi gim."))
+        })
+        .collect();
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "কাখি গিম।");
+    }
+}
+
+#[test]
+fn test_convert_batch_keeps_order() {
+    let mim = load_sample();
+    let lines: Vec<String> = (0..1000)
+        .map(|i| ["ka", "khi", "gim.", "nr", "o"][i % 5].repeat(i % 7 + 1))
+        .collect();
+    let options = BatchOptions {
+        threads: 4,
+        chunk_size: 64,
+        ..BatchOptions::default()
+    };
+
+    let mut batch = convert_batch(mim.im_info(), &lines, &options);
+    let converted: Vec<String> = batch.by_ref().collect();
+    let expected: Vec<String> = lines.iter().map(|line| mim.convert(line)).collect();
+    assert_eq!(converted, expected);
+
+    let stats = batch.stats();
+    assert_eq!(stats.units, 1000);
+    assert_eq!(
+        stats.input_bytes,
+        lines.iter().map(String::len).sum::<usize>()
+    );
+    assert_eq!(
+        stats.output_bytes,
+        expected.iter().map(String::len).sum::<usize>()
+    );
+}
+
+#[test]
+fn test_convert_reader_lines() {
+    let mim = load_sample();
+    let input = "kakhi\r\n\ngim.\nk";
+    let mut output = Vec::new();
+
+    let stats = convert_reader(
+        mim.im_info(),
+        input.as_bytes(),
+        &mut output,
+        &BatchOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap(), "কাখি\r\n\nগিম।\nক");
+    assert_eq!(stats.units, 4);
+    assert_eq!(stats.input_bytes, input.len());
+}
+
+#[test]
+fn test_convert_reader_paragraphs() {
+    let mim = load_sample();
+    let input = "\nk\na\n\n  \nka\n";
+    let mut lines = Vec::new();
+    let mut paragraphs = Vec::new();
+
+    convert_reader(
+        mim.im_info(),
+        input.as_bytes(),
+        &mut lines,
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    let stats = convert_reader(
+        mim.im_info(),
+        input.as_bytes(),
+        &mut paragraphs,
+        &BatchOptions {
+            boundary: Boundary::Paragraph,
+            ..BatchOptions::default()
+        },
+    )
+    .unwrap();
+
+    // The consonant state carries over the line break only within a
+    // paragraph
+    assert_eq!(String::from_utf8(lines).unwrap(), "\nক\nআ\n\n  \nকা\n");
+    assert_eq!(String::from_utf8(paragraphs).unwrap(), "\nক\nা\n\n  \nকা\n");
+    assert_eq!(stats.units, 3);
+}
@@ -0,0 +1,75 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::bytecode::{BinaryOp, Compiler, Op, Position, Symbols};
+use m17n_mim_rs::lisp_parser::{Element, parse_mim};
+use std::fs;
+
+fn compile(actions: &str) -> (Vec<Op>, Symbols, Symbols) {
+    let mut variables = Symbols::default();
+    let mut states = Symbols::default();
+    let Element::List(actions) = parse_mim(actions).unwrap() else {
+        unreachable!()
+    };
+    let program = Compiler {
+        variables: &mut variables,
+        states: &mut states,
+    }
+    .compile(&actions);
+    (program.ops, variables, states)
+}
+
+#[test]
+fn test_compile_actions() {
+    let (ops, variables, states) =
+        compile(r#""ক" ?া (set X (+ @-1 1 2)) (delete @-) (insert X) (shift second) (beep)"#);
+
+    assert_eq!(
+        ops,
+        [
+            Op::Insert("ক".into()),
+            Op::InsertChar('া'),
+            Op::PushSurrounding(-1),
+            Op::PushInt(1),
+            Op::Binary(BinaryOp::Add),
+            Op::PushInt(2),
+            Op::Binary(BinaryOp::Add),
+            Op::Set(0),
+            Op::Delete(Position::Prev),
+            Op::InsertVar(0),
+            Op::Shift(0),
+            Op::Unknown("beep".into()),
+        ]
+    );
+    assert_eq!(variables.name(0), Some("X"));
+    assert_eq!(states.name(0), Some("second"));
+}
+
+#[test]
+fn test_compile_cond() {
+    let (ops, _, _) = compile(r#"(cond ((= X 1) "a") (1 "b" (commit)))"#);
+
+    assert_eq!(
+        ops,
+        [
+            Op::PushVar(0),
+            Op::PushInt(1),
+            Op::Binary(BinaryOp::Eq),
+            Op::JumpIfZero(6),
+            Op::Insert("a".into()),
+            Op::Jump(11),
+            Op::PushInt(1),
+            Op::JumpIfZero(11),
+            Op::Insert("b".into()),
+            Op::Commit,
+            Op::Jump(11),
+        ]
+    );
+}
+
+#[test]
+fn test_titlecase_conversion() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/titlecase.mim").unwrap());
+
+    assert_eq!(mim.convert("a quick brown fox"), "A Quick Brown Fox");
+    assert_eq!(mim.convert("iistanbul"), "İstanbul");
+    assert_eq!(mim.convert("xiix"), "Xix");
+}
@@ -0,0 +1,143 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::char_names::CharNames;
+use m17n_mim_rs::cheatsheet::*;
+use std::fs;
+
+const UNICODE_DATA: &str = "\
+0000;<control>;Cc;0;BN;;;;;N;NULL;;;;
+0041;LATIN CAPITAL LETTER A;Lu;0;L;;;;;N;;;;0061;
+0995;BENGALI LETTER KA;Lo;0;L;;;;;N;;;;;
+09BF;BENGALI VOWEL SIGN I;Mc;0;L;;;;;N;;;;;
+4E00;<CJK Ideograph, First>;Lo;0;L;;;;;N;;;;;
+9FFF;<CJK Ideograph, Last>;Lo;0;L;;;;;N;;;;;
+AC00;<Hangul Syllable, First>;Lo;0;L;;;;;N;;;;;
+D7A3;<Hangul Syllable, Last>;Lo;0;L;;;;;N;;;;;
+E000;<Private Use, First>;Co;0;L;;;;;N;;;;;
+F8FF;<Private Use, Last>;Co;0;L;;;;;N;;;;;
+";
+
+const SHEET: &str = r#"(input-method t sheet)
+(map
+  (m ("a" "A") ("|" "<&>") ("c" (cond ((= X 1) "x" (delete @-)) (1 "y"))) ("`" "b" (insert V)))
+  (unused ("z" "Z")))
+(state (init (m)))"#;
+
+#[test]
+fn test_char_names() {
+    let names = CharNames::from_unicode_data(UNICODE_DATA);
+    assert_eq!(names.name('A').as_deref(), Some("LATIN CAPITAL LETTER A"));
+    assert_eq!(names.name('\0'), None);
+    assert_eq!(
+        names.name('\u{4E01}').as_deref(),
+        Some("CJK UNIFIED IDEOGRAPH-4E01")
+    );
+    assert_eq!(names.name('한').as_deref(), Some("HANGUL SYLLABLE HAN"));
+    assert_eq!(names.name('\u{E000}'), None);
+    assert_eq!(names.len(), 3);
+}
+
+#[test]
+fn test_sections() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let sheet = cheat_sheet(mim.im_info());
+    assert_eq!(sheet.title, "Bengali Sample");
+
+    // Maps in the order the states use them
+    let sections: Vec<_> = sheet
+        .sections
+        .iter()
+        .map(|section| {
+            (
+                section.map_name.as_str(),
+                section.states.len(),
+                section.entries.len(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        sections,
+        [
+            ("consonant", 2, 6),
+            ("vowel", 2, 3),
+            ("punct", 2, 1),
+            ("sign", 1, 3)
+        ]
+    );
+
+    let mim = M17nMim::new(SHEET);
+    let sheet = cheat_sheet(mim.im_info());
+    assert_eq!(
+        sheet.sections[0].entries[2],
+        SheetEntry {
+            keyseq: "c".into(),
+            output: "".into(),
+            notes: vec![
+                "cond: inserts one of \"x\", \"y\"".into(),
+                "deletes text".into()
+            ],
+        }
+    );
+}
+
+#[test]
+fn test_markdown() {
+    let names = CharNames::from_unicode_data(UNICODE_DATA);
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let markdown = cheat_sheet(mim.im_info()).to_markdown(&names);
+    assert!(
+        markdown
+            .starts_with("# Bengali Sample\n\n## consonant\n\nStates: init, after-consonant\n\n")
+    );
+    assert!(markdown.contains(concat!(
+        "| Keys | Output | Code points | Names |\n",
+        "|---|---|---|---|\n",
+        "| `k` | ক | U+0995 | BENGALI LETTER KA |\n",
+        "| `kh` | খ | U+0996 | ? |\n",
+    )));
+
+    // Without names there is no names column
+    let mim = M17nMim::new(SHEET);
+    assert_eq!(
+        cheat_sheet(mim.im_info()).to_markdown(&CharNames::new()),
+        concat!(
+            "# sheet\n",
+            "\n## m\n\n",
+            "States: init\n\n",
+            "| Keys | Output | Code points | Notes |\n",
+            "|---|---|---|---|\n",
+            "| `a` | A | U+0041 |  |\n",
+            "| `\\|` | <&> | U+003C U+0026 U+003E |  |\n",
+            "| `c` |  |  | cond: inserts one of \"x\", \"y\"; deletes text |\n",
+            "| `` ` `` | b | U+0062 | inserts the character in variable V |\n",
+            "\n## unused\n\n",
+            "Not used by any state.\n\n",
+            "| Keys | Output | Code points |\n",
+            "|---|---|---|\n",
+            "| `z` | Z | U+005A |\n",
+        )
+    );
+}
+
+#[test]
+fn test_html() {
+    let mut mim = M17nMim::new(SHEET);
+    mim.load_char_names(UNICODE_DATA);
+    let html = mim.cheat_sheet_html();
+    assert!(html.starts_with(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>sheet</title>\n"
+    ));
+    assert!(html.contains("@media print"));
+    assert!(html.contains(concat!(
+        "<tr><td><kbd>|</kbd></td><td>&lt;&amp;&gt;</td>",
+        "<td class=\"cp\">U+003C U+0026 U+003E</td><td class=\"name\">?, ?, ?</td>",
+        "<td class=\"note\"></td></tr>\n"
+    )));
+    assert!(html.ends_with("</table>\n</section>\n</body>\n</html>\n"));
+
+    // The page is in the input method's language
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    assert!(
+        mim.cheat_sheet_html()
+            .starts_with("<!DOCTYPE html>\n<html lang=\"bn\">\n")
+    );
+}
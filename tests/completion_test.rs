@@ -0,0 +1,83 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::completion::*;
+use m17n_mim_rs::session::Session;
+
+const MIM: &str = r#"(input-method t complete)
+(map
+  (first ("k" "ক") ("kh" "খ") ("khh" "ক্ষ") ("g" "গ"))
+  (second ("kx" "x") ("kh" "lost") ("q" "q")))
+(state
+  (init (first (shift other)) (second))
+  (other (second)))"#;
+
+fn keys(found: &[Completion]) -> Vec<(&str, &str, &str)> {
+    found
+        .iter()
+        .map(|c| (c.keyseq.as_str(), c.rest.as_str(), c.output.as_str()))
+        .collect()
+}
+
+#[test]
+fn test_completions() {
+    let mim = M17nMim::new(MIM);
+    let im_info = mim.im_info();
+    let init = im_info.initial_state();
+
+    // Shortest first; "kh" of the later branch never fires
+    let found = completions(im_info, init, &['k']);
+    assert_eq!(
+        keys(&found),
+        [
+            ("k", "", "ক"),
+            ("kh", "h", "খ"),
+            ("kx", "x", "x"),
+            ("khh", "hh", "ক্ষ"),
+        ]
+    );
+    assert_eq!(found[2].map_name, "second");
+    assert_eq!(found[2].rule, 0);
+
+    assert_eq!(
+        keys(&completions(im_info, init, &['k', 'h'])),
+        [("kh", "", "খ"), ("khh", "h", "ক্ষ")]
+    );
+    assert_eq!(completions(im_info, init, &['z']), []);
+
+    let other = im_info.state("other").unwrap().id;
+    assert_eq!(
+        keys(&completions(im_info, other, &['k'])),
+        [("kh", "h", "lost"), ("kx", "x", "x")]
+    );
+}
+
+#[test]
+fn test_session_completions() {
+    let mim = M17nMim::new(MIM);
+    let im_info = mim.im_info();
+    let mut session = Session::new(im_info);
+
+    session.handle_key(im_info, 'k');
+    session.handle_key(im_info, 'h');
+    assert_eq!(session.pending(), ['k', 'h']);
+    assert_eq!(
+        keys(&session.completions(im_info)),
+        [("kh", "", "খ"), ("khh", "h", "ক্ষ")]
+    );
+}
+
+#[test]
+fn test_json() {
+    let mim = M17nMim::new(MIM);
+    assert_eq!(
+        mim.completions("init", "kh"),
+        concat!(
+            r#"[{"keyseq":"kh","rest":"","output":"খ","map":"first","rule":1},"#,
+            r#"{"keyseq":"khh","rest":"h","output":"ক্ষ","map":"first","rule":2}]"#
+        )
+    );
+    assert_eq!(mim.completions("nowhere", "k"), "[]");
+
+    let mut session = mim.create_session();
+    session.handle_keys("kh");
+    assert_eq!(session.get_completions(), mim.completions("init", "kh"));
+}
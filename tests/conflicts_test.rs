@@ -0,0 +1,144 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::conflicts::*;
+use std::fs;
+
+#[test]
+fn test_prefix_conflicts() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let report = find_conflicts(mim.im_info());
+    assert_eq!(report.dead_rules, []);
+    assert_eq!(
+        report.conflicts,
+        [
+            PrefixConflict {
+                state: "init".into(),
+                keys: "kh".into(),
+                prefix: "k".into(),
+                example: "kh".into(),
+                batch: "খ".into(),
+                prefix_first: "কh".into(),
+            },
+            PrefixConflict {
+                state: "after-consonant".into(),
+                keys: "kh".into(),
+                prefix: "k".into(),
+                example: "gkh".into(),
+                batch: "গখ".into(),
+                prefix_first: "গকh".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        report.to_string(),
+        concat!(
+            "state init: \"kh\" hides \"k\" followed by \"h\": \"kh\" gives \"খ\", not \"কh\"\n",
+            "state after-consonant: \"kh\" hides \"k\" followed by \"h\": \"gkh\" gives \"গখ\", not \"গকh\"\n",
+        )
+    );
+}
+
+#[test]
+fn test_same_result_is_no_conflict() {
+    let mim = M17nMim::new(
+        r#"(input-method t prefix)
+(map (m ("a" "x") ("ab" "xy") ("b" "y")))
+(state (init (m)))"#,
+    );
+    assert_eq!(find_conflicts(mim.im_info()), ConflictReport::default());
+}
+
+#[test]
+fn test_dead_rules() {
+    let mim = M17nMim::new(
+        r#"(input-method t dead)
+(map
+  (first ("a" "x") ("b" (shift second)))
+  (other ("a" "y") ("c" "z"))
+  (unused ("u" "u"))
+  (redefined ("r" "1") ("r" "2")))
+(state
+  (init (first) (redefined))
+  (second (first) (other)))"#,
+    );
+    let report = find_conflicts(mim.im_info());
+    assert_eq!(report.conflicts, []);
+    assert_eq!(
+        report.dead_rules,
+        [
+            DeadRule {
+                map_name: "other".into(),
+                rule: 0,
+                keyseq: Some("a".into()),
+                reason: DeadReason::Shadowed {
+                    example: "ba".into(),
+                    map_name: "first".into(),
+                    rule: 0,
+                },
+            },
+            DeadRule {
+                map_name: "redefined".into(),
+                rule: 0,
+                keyseq: None,
+                reason: DeadReason::Redefined,
+            },
+            DeadRule {
+                map_name: "unused".into(),
+                rule: 0,
+                keyseq: Some("u".into()),
+                reason: DeadReason::MapNotUsed,
+            },
+        ]
+    );
+    assert_eq!(
+        report.to_string(),
+        concat!(
+            "map other, rule 0 \"a\" never fires: \"ba\" fires map first, rule 0 instead\n",
+            "map redefined, rule 0 never fires: a later rule has the same KEYSEQ\n",
+            "map unused, rule 0 \"u\" never fires: no state uses the map\n",
+        )
+    );
+}
+
+#[test]
+fn test_states_reached_through_nil() {
+    let mim = M17nMim::new(
+        r#"(input-method t nil)
+(map (m ("a" "A")) (n ("b" "B")))
+(state
+  (init (m) (nil (shift second)))
+  (second (n)))"#,
+    );
+    assert_eq!(mim.convert(" b"), " B");
+    assert_eq!(find_conflicts(mim.im_info()), ConflictReport::default());
+}
+
+#[test]
+fn test_states_not_reached() {
+    // Only "ab" leads to state hidden, through a `cond` on what "a" set
+    let mim = M17nMim::new(
+        r#"(input-method t cond)
+(map
+  (m ("a" (set X 1)) ("b" (cond ((= X 1) (shift hidden)))))
+  (h ("c" "C")))
+(state
+  (init (m))
+  (hidden (h)))"#,
+    );
+    assert_eq!(mim.convert("abc"), "C");
+    let report = find_conflicts(mim.im_info());
+    assert_eq!(
+        report.dead_rules,
+        [DeadRule {
+            map_name: "h".into(),
+            rule: 0,
+            keyseq: Some("c".into()),
+            reason: DeadReason::NotReached {
+                state: "hidden".into(),
+            },
+        }]
+    );
+    assert_eq!(
+        report.to_string(),
+        "map h, rule 0 \"c\" not reached by the analysis: state hidden uses the map\n"
+    );
+}
@@ -0,0 +1,112 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::coverage::*;
+use std::fs;
+
+fn load(path: &str) -> M17nMim {
+    M17nMim::new(&fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn test_rule_and_branch_hits() {
+    let mim = load("tests/fixtures/bn-sample.mim");
+    let coverage = collect_coverage(mim.im_info(), ["kakhi gim.", "ko"]);
+    assert_eq!(coverage.inputs, 2);
+    assert_eq!(coverage.unmatched_keys, 1);
+
+    let consonant = &coverage.maps[0];
+    assert_eq!(consonant.name, "consonant");
+    let hits: Vec<_> = consonant
+        .rules
+        .iter()
+        .map(|rule| (rule.keyseq.as_deref().unwrap(), rule.hits))
+        .collect();
+    assert_eq!(
+        hits,
+        [("k", 2), ("kh", 1), ("g", 1), ("m", 1), ("n", 0), ("r", 0)]
+    );
+
+    let after_consonant = &coverage.states[1];
+    assert_eq!(after_consonant.name, "after-consonant");
+    let hits: Vec<_> = after_consonant
+        .branches
+        .iter()
+        .map(|branch| (branch.map_name.as_str(), branch.hits))
+        .collect();
+    assert_eq!(
+        hits,
+        [("sign", 4), ("consonant", 0), ("vowel", 0), ("punct", 1)]
+    );
+
+    assert_eq!(
+        coverage.unused_rules(),
+        [
+            ("consonant", 4, Some("n")),
+            ("consonant", 5, Some("r")),
+            ("vowel", 0, Some("a")),
+            ("vowel", 1, Some("i")),
+            ("vowel", 2, Some("o")),
+        ]
+    );
+}
+
+#[test]
+fn test_cond_arm_hits() {
+    let mim = load("tests/fixtures/titlecase.mim");
+    let mut coverage = Coverage::new(mim.im_info());
+    coverage.add("ab");
+    // The outer arm and the arm for other letters
+    assert_eq!(coverage.states[0].branches[0].arms, [1, 0, 1]);
+
+    coverage.add("xii");
+    assert_eq!(coverage.states[0].branches[0].arms, [2, 1, 1]);
+    assert_eq!(coverage.states[0].branches[0].hits, 4);
+}
+
+#[test]
+fn test_redefined_rule() {
+    let mim = M17nMim::new(
+        r#"(input-method t redefined)
+(map (m ("a" "x") ("b" "y") ("a" "z")))
+(state (init (m)))"#,
+    );
+    let coverage = collect_coverage(mim.im_info(), ["ab"]);
+    let rules = &coverage.maps[0].rules;
+    assert_eq!(rules[0].keyseq, None);
+    assert_eq!(rules[2].keyseq.as_deref(), Some("a"));
+    assert_eq!(coverage.unused_rules(), [("m", 0, None)]);
+}
+
+#[test]
+fn test_coverage_text_and_json() {
+    let mim = M17nMim::new(
+        r#"(input-method t report)
+(map (m ("a" "x") ("b" (cond ((= @-1 ?x) "y") (1 "z")))))
+(state (init (m)))"#,
+    );
+    let coverage = collect_coverage(mim.im_info(), ["ab"]);
+    assert_eq!(
+        coverage.to_string(),
+        concat!(
+            "1 inputs, 0 unmatched keys: 2/2 rules, 1/1 branches, 1/2 cond arms hit\n",
+            "\n",
+            "map m\n",
+            "       1    0 \"a\"\n",
+            "       1    1 \"b\"\n",
+            "       1      cond arm 0\n",
+            "       0      cond arm 1\n",
+            "\n",
+            "state init\n",
+            "       2    0 m\n",
+        )
+    );
+    assert_eq!(
+        coverage.to_json(),
+        concat!(
+            r#"{"inputs":1,"unmatched_keys":0,"maps":[{"name":"m","rules":["#,
+            r#"{"rule":0,"keyseq":"a","hits":1,"arms":[]},"#,
+            r#"{"rule":1,"keyseq":"b","hits":1,"arms":[1,0]}]}],"#,
+            r#""states":[{"name":"init","branches":[{"branch":0,"map":"m","hits":2,"arms":[]}]}],"#,
+            r#""unused_rules":[]}"#
+        )
+    );
+}
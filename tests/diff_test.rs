@@ -0,0 +1,232 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::diff::*;
+
+const OLD: &str = r#"(input-method bn diff)
+(title "Diff")
+(map
+  (consonant ("k" "ক") ("kh" "খ") ("x" "ক্ষ"))
+  (vowel ("i" "ি") ("a" "া"))
+  (symbol ("." "।")))
+(state
+  (init (consonant) (vowel) (symbol))
+  (other (vowel)))"#;
+
+const NEW: &str = r#"(input-method bn diff)
+(title "Diff 2")
+(map
+  (consonant ("k" "ক") ("kh" "খ") ("g" "গ") ("x" "ক্") ("x" "ক্ষ্"))
+  (vowel ("i" "ি") ("a" "া"))
+  (digit ("1" "১")))
+(state
+  (init (vowel) (consonant (shift other)) (digit))
+  (third (vowel)))"#;
+
+#[test]
+fn test_identical() {
+    let old = M17nMim::new(OLD);
+    let diff = compare(old.im_info(), M17nMim::new(OLD).im_info(), 10);
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "");
+}
+
+#[test]
+fn test_rules_and_metadata() {
+    let old = M17nMim::new(OLD);
+    let new = M17nMim::new(NEW);
+    let diff = compare(old.im_info(), new.im_info(), 0);
+
+    assert_eq!(
+        diff.metadata,
+        [FieldChange {
+            field: "title".into(),
+            before: "Diff".into(),
+            after: "Diff 2".into(),
+        }]
+    );
+
+    let maps: Vec<(&str, Change)> = diff
+        .maps
+        .iter()
+        .map(|m| (m.name.as_str(), m.change))
+        .collect();
+    assert_eq!(
+        maps,
+        [
+            ("consonant", Change::Changed),
+            ("digit", Change::Added),
+            ("symbol", Change::Removed),
+        ]
+    );
+    // "x" is redefined in the new version; only the later rule counts
+    assert_eq!(
+        diff.maps[0].rules,
+        [
+            RuleDiff {
+                keyseq: "g".into(),
+                change: Change::Added,
+                before: String::new(),
+                after: "insert \"গ\"".into(),
+            },
+            RuleDiff {
+                keyseq: "x".into(),
+                change: Change::Changed,
+                before: "insert \"ক্ষ\"".into(),
+                after: "insert \"ক্ষ্\"".into(),
+            },
+        ]
+    );
+    assert!(diff.inputs.is_empty());
+}
+
+#[test]
+fn test_states() {
+    let old = M17nMim::new(OLD);
+    let new = M17nMim::new(NEW);
+    let diff = compare(old.im_info(), new.im_info(), 0);
+
+    let states: Vec<(&str, Change)> = diff
+        .states
+        .iter()
+        .map(|s| (s.name.as_str(), s.change))
+        .collect();
+    assert_eq!(
+        states,
+        [
+            ("init", Change::Changed),
+            ("other", Change::Removed),
+            ("third", Change::Added),
+        ]
+    );
+
+    let init = &diff.states[0];
+    let branches: Vec<(&str, Change)> = init
+        .branches
+        .iter()
+        .map(|b| (b.map_name.as_str(), b.change))
+        .collect();
+    assert_eq!(
+        branches,
+        [
+            ("consonant", Change::Changed),
+            ("digit", Change::Added),
+            ("symbol", Change::Removed),
+        ]
+    );
+    assert_eq!(init.branches[0].before, "(no actions)");
+    assert_eq!(init.branches[0].after, "shift other");
+    assert_eq!(
+        init.order,
+        Some((
+            vec!["consonant".to_string(), "vowel".to_string()],
+            vec!["vowel".to_string(), "consonant".to_string()],
+        ))
+    );
+}
+
+#[test]
+fn test_variables_by_name() {
+    // The same actions, with variables interned in another order
+    let old = M17nMim::new(
+        r#"(input-method t vars)
+(map (m ("a" (set x 1) (set y 2)) ("b" (set y 3))))
+(state (init (m)))"#,
+    );
+    let new = M17nMim::new(
+        r#"(input-method t vars)
+(map (m ("b" (set y 3)) ("a" (set x 1) (set y 2))))
+(state (init (m)))"#,
+    );
+    assert!(compare(old.im_info(), new.im_info(), 10).is_empty());
+}
+
+#[test]
+fn test_sample_inputs() {
+    let old = M17nMim::new(OLD);
+    let new = M17nMim::new(NEW);
+    let diff = compare(old.im_info(), new.im_info(), 10);
+    assert_eq!(
+        diff.inputs,
+        [
+            InputDiff {
+                input: ".".into(),
+                before: "।".into(),
+                after: ".".into(),
+            },
+            InputDiff {
+                input: "1".into(),
+                before: "1".into(),
+                after: "১".into(),
+            },
+            InputDiff {
+                input: "g".into(),
+                before: "g".into(),
+                after: "গ".into(),
+            },
+            InputDiff {
+                input: "x".into(),
+                before: "ক্ষ".into(),
+                after: "ক্ষ্".into(),
+            },
+        ]
+    );
+    assert_eq!(compare(old.im_info(), new.im_info(), 2).inputs.len(), 2);
+
+    assert_eq!(
+        diff.to_string(),
+        r#"~ title: "Diff" -> "Diff 2"
+~ map consonant
+  + "g": insert "গ"
+  ~ "x": insert "ক্ষ" -> insert "ক্ষ্"
++ map digit
+  + "1": insert "১"
+- map symbol
+  - ".": insert "।"
+~ state init
+  ~ branch consonant: (no actions) -> shift other
+  + branch digit: (no actions)
+  - branch symbol: (no actions)
+  ~ branch order: consonant, vowel -> vowel, consonant
+- state other
++ state third
+inputs that convert differently:
+  ".": "।" -> "."
+  "1": "1" -> "১"
+  "g": "g" -> "গ"
+  "x": "ক্ষ" -> "ক্ষ্"
+"#
+    );
+}
+
+#[test]
+fn test_changed_cond() {
+    let old = M17nMim::new(
+        r#"(input-method t cond)
+(map (m ("a" (set X 1) (cond ((= X 1) "x") (1 (set X (+ X 1 2)) (commit))) "z")))
+(state (init (m)))"#,
+    );
+    let new = M17nMim::new(
+        r#"(input-method t cond)
+(map (m ("a" (set X 1) (cond ((= X 2) "x") (1 (cond ((! @-1) (delete @-))))) "z")))
+(state (init (m)))"#,
+    );
+    let diff = compare(old.im_info(), new.im_info(), 0);
+    assert_eq!(
+        diff.maps[0].rules,
+        [RuleDiff {
+            keyseq: "a".into(),
+            change: Change::Changed,
+            before: concat!(
+                "set X 1, ",
+                "cond ((= X 1) (insert \"x\")) (1 (set X (+ X 1 2)) (commit)), ",
+                "insert \"z\""
+            )
+            .into(),
+            after: concat!(
+                "set X 1, ",
+                "cond ((= X 2) (insert \"x\")) (1 (cond ((! @-1) (delete @-)))), ",
+                "insert \"z\""
+            )
+            .into(),
+        }]
+    );
+}
@@ -0,0 +1,138 @@
+use m17n_mim_rs::gettext::{Catalog, Catalogs, locale_fallbacks};
+use m17n_mim_rs::im_info::load_im_info;
+use m17n_mim_rs::lisp_parser::parse_mim;
+use std::fs;
+
+const BN_PO: &str = r#"
+# Bengali translations for m17n-db.
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "Bengali Sample"
+msgstr "বাংলা নমুনা"
+
+msgid "Sample Bengali "
+"phonetic input method"
+msgstr "নমুনা বাংলা "
+"ধ্বনিভিত্তিক ইনপুট পদ্ধতি"
+
+msgid "Octal \101"
+msgstr "\340\246\205\a\"\\"
+
+#, fuzzy
+msgid "Titlecase letters"
+msgstr "ভুল অনুবাদ"
+"#;
+
+/// Build a little-endian .mo file from (msgid, msgstr) pairs.
+fn build_mo(entries: &[(&str, &str)]) -> Vec<u8> {
+    let count = entries.len() as u32;
+    let ids_offset = 28;
+    let strs_offset = ids_offset + count * 8;
+    let mut data_offset = strs_offset + count * 8;
+
+    let mut header = Vec::new();
+    for value in [0x950412de, 0, count, ids_offset, strs_offset, 0, 0] {
+        header.extend_from_slice(&u32::to_le_bytes(value));
+    }
+
+    let mut ids = Vec::new();
+    let mut strs = Vec::new();
+    let mut data = Vec::new();
+    for table in [0, 1] {
+        for (msgid, msgstr) in entries {
+            let s = if table == 0 { msgid } else { msgstr };
+            let target = if table == 0 { &mut ids } else { &mut strs };
+            target.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            target.extend_from_slice(&data_offset.to_le_bytes());
+            data.extend_from_slice(s.as_bytes());
+            data.push(0);
+            data_offset += s.len() as u32 + 1;
+        }
+    }
+
+    [header, ids, strs, data].concat()
+}
+
+#[test]
+fn test_po_catalog() {
+    let catalog = Catalog::from_po(BN_PO);
+
+    assert_eq!(catalog.get("Bengali Sample"), Some("বাংলা নমুনা"));
+    assert_eq!(
+        catalog.get("Sample Bengali phonetic input method"),
+        Some("নমুনা বাংলা ধ্বনিভিত্তিক ইনপুট পদ্ধতি")
+    );
+    // Octal escapes are bytes of UTF-8
+    assert_eq!(catalog.get("Octal A"), Some("অ\u{7}\"\\"));
+    assert_eq!(catalog.get("Titlecase letters"), None);
+    assert_eq!(catalog.len(), 3);
+}
+
+#[test]
+fn test_mo_catalog() {
+    let mo = build_mo(&[
+        ("", "Content-Type: text/plain\n"),
+        ("Bengali Sample", "বাংলা নমুনা"),
+    ]);
+    let catalog = Catalog::from_mo(&mo).expect("valid .mo file");
+
+    assert_eq!(catalog.get("Bengali Sample"), Some("বাংলা নমুনা"));
+    assert_eq!(catalog.len(), 1);
+    assert!(Catalog::from_mo(b"not a catalog").is_none());
+
+    // Lengths and offsets near u32::MAX must not overflow on 32-bit targets
+    let mut bad = mo.clone();
+    bad[36..44].copy_from_slice(&[0xff; 8]);
+    assert!(Catalog::from_mo(&bad).is_none());
+    let mut bad = mo;
+    bad[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Catalog::from_mo(&bad).is_none());
+}
+
+#[test]
+fn test_locale_fallbacks() {
+    assert_eq!(
+        locale_fallbacks("bn_BD.UTF-8@latin"),
+        [
+            "bn_BD.UTF-8@latin",
+            "bn_BD@latin",
+            "bn@latin",
+            "bn_BD.UTF-8",
+            "bn_BD",
+            "bn"
+        ]
+    );
+    assert_eq!(locale_fallbacks("bn"), ["bn"]);
+
+    // A catalog for the modifier wins over one for the territory
+    let mut catalogs = Catalogs::new();
+    catalogs.insert("bn_BD", Catalog::from_po("msgid \"a\"\nmsgstr \"আ\""));
+    catalogs.insert("bn@latin", Catalog::from_po("msgid \"a\"\nmsgstr \"aa\""));
+    assert_eq!(catalogs.translate("bn_BD.UTF-8@latin", "a"), "aa");
+    assert_eq!(catalogs.translate("bn_BD.UTF-8", "a"), "আ");
+}
+
+#[test]
+fn test_localized_im_info() {
+    let mim = fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap();
+    let im_info = load_im_info(parse_mim(&mim).unwrap());
+
+    assert!(im_info.title.translatable);
+    assert_eq!(im_info.title.text, "Bengali Sample");
+    assert!(im_info.description.translatable);
+
+    let mut catalogs = Catalogs::new();
+    catalogs.insert("bn", Catalog::from_po(BN_PO));
+
+    assert_eq!(im_info.localized_title(&catalogs, "bn_IN.UTF-8"), "বাংলা নমুনা");
+    assert_eq!(
+        im_info.localized_description(&catalogs, "bn_BD"),
+        "নমুনা বাংলা ধ্বনিভিত্তিক ইনপুট পদ্ধতি"
+    );
+    assert_eq!(
+        im_info.localized_title(&catalogs, "fr_FR"),
+        "Bengali Sample"
+    );
+}
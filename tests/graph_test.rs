@@ -0,0 +1,101 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::graph::*;
+use std::fs;
+
+const SHIFTS: &str = r#"(input-method t graph)
+(map
+  (m ("a" "A" (shift second)) ("\"" "B" (shift second)) ("c" (cond ((= X 1) (shift third)))))
+  (n ("x" "X")))
+(state
+  (init (m))
+  (second (n (shift init)) (nil (shift third)))
+  (third (n)))"#;
+
+#[test]
+fn test_state_graph() {
+    let mim = M17nMim::new(SHIFTS);
+    let graph = state_graph(mim.im_info());
+    assert_eq!(graph.states, ["init", "second", "third"]);
+
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|edge| {
+            (
+                edge.from.as_str(),
+                edge.to.as_str(),
+                edge.trigger.label(),
+                edge.conditional,
+            )
+        })
+        .collect();
+    assert_eq!(
+        edges,
+        [
+            ("init", "second", "m \"a\"".to_string(), false),
+            ("init", "second", "m \"\"\"".to_string(), false),
+            ("init", "third", "m \"c\"".to_string(), true),
+            ("second", "init", "n".to_string(), false),
+            ("second", "third", "nil".to_string(), false),
+        ]
+    );
+    assert_eq!(
+        graph.edges[2].trigger,
+        Trigger::Rule {
+            map_name: "m".into(),
+            rule: 2,
+            keyseq: "c".into(),
+        }
+    );
+}
+
+#[test]
+fn test_dot() {
+    let mim = M17nMim::new(SHIFTS);
+    assert_eq!(
+        state_graph(mim.im_info()).to_dot(),
+        concat!(
+            "digraph states {\n",
+            "    rankdir=LR;\n",
+            "    \"init\" [peripheries=2];\n",
+            "    \"second\";\n",
+            "    \"third\";\n",
+            "    \"init\" -> \"second\" [label=\"m \\\"a\\\"\"];\n",
+            "    \"init\" -> \"second\" [label=\"m \\\"\\\"\\\"\"];\n",
+            "    \"init\" -> \"third\" [label=\"m \\\"c\\\"\", style=dashed];\n",
+            "    \"second\" -> \"init\" [label=\"n\"];\n",
+            "    \"second\" -> \"third\" [label=\"nil\"];\n",
+            "}\n",
+        )
+    );
+
+    // Shifts in branch actions are labeled with the map
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let dot = state_graph(mim.im_info()).to_dot();
+    assert!(dot.contains("    \"init\" -> \"after-consonant\" [label=\"consonant\"];\n"));
+    assert!(dot.contains("    \"after-consonant\" -> \"init\" [label=\"punct\"];\n"));
+}
+
+#[test]
+fn test_collapsed_mermaid() {
+    let mim = M17nMim::new(SHIFTS);
+    let graph = state_graph(mim.im_info()).collapsed();
+    assert_eq!(graph.edges.len(), 4);
+    assert_eq!(
+        graph.to_mermaid(),
+        concat!(
+            "flowchart LR\n",
+            "    s0([\"init\"])\n",
+            "    s1[\"second\"]\n",
+            "    s2[\"third\"]\n",
+            "    s0 -->|\"m\"| s1\n",
+            "    s0 -.->|\"m\"| s2\n",
+            "    s1 -->|\"n\"| s0\n",
+            "    s1 -->|\"nil\"| s2\n",
+        )
+    );
+
+    // Rule labels keep their KEYSEQ, with quotes escaped
+    let mermaid = state_graph(mim.im_info()).to_mermaid();
+    assert!(mermaid.contains("    s0 -->|\"m #quot;#quot;#quot;\"| s1\n"));
+}
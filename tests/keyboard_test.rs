@@ -0,0 +1,88 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::keyboard::*;
+use std::fs;
+
+fn label(layout: &KeyboardLayout, state: &str, key: char) -> KeyLabel {
+    layout
+        .states
+        .iter()
+        .find(|layout| layout.state == state)
+        .unwrap()
+        .keys
+        .iter()
+        .flat_map(|k| [&k.base, &k.shifted])
+        .find(|label| label.key == key)
+        .cloned()
+        .unwrap()
+}
+
+#[test]
+fn test_layout() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let layout = keyboard_layout(mim.im_info(), US_QWERTY);
+    assert_eq!(layout.states.len(), 2);
+    assert_eq!(layout.states[0].keys.len(), 47);
+
+    // "k" types ক, but "kh" types খ
+    assert_eq!(
+        label(&layout, "init", 'k'),
+        KeyLabel {
+            key: 'k',
+            output: Some("ক".into()),
+            starts_sequence: true,
+            next_state: Some("after-consonant".into()),
+        }
+    );
+    assert_eq!(
+        label(&layout, "init", 'K'),
+        KeyLabel {
+            key: 'K',
+            output: None,
+            starts_sequence: false,
+            next_state: None,
+        }
+    );
+    assert_eq!(label(&layout, "init", 'a').output.as_deref(), Some("আ"));
+    assert_eq!(label(&layout, "init", 'a').next_state, None);
+
+    // After a consonant, vowel keys type signs
+    let sign = label(&layout, "after-consonant", 'a');
+    assert_eq!(sign.output.as_deref(), Some("া"));
+    assert_eq!(sign.next_state.as_deref(), Some("init"));
+    assert!(!sign.starts_sequence);
+}
+
+#[test]
+fn test_custom_keys() {
+    let mim = M17nMim::new(
+        r#"(input-method t keys)
+(map (m ("a" "x") ("A" "y") ("ab" "z") ("b" (cond ((= X 1) "p") (1 "q")))))
+(state (init (m)))"#,
+    );
+    let layout = keyboard_layout(mim.im_info(), &[('a', 'A'), ('b', 'B')]);
+    let keys = &layout.states[0].keys;
+    assert_eq!(keys[0].base.output.as_deref(), Some("x"));
+    assert!(keys[0].base.starts_sequence);
+    assert_eq!(keys[0].shifted.output.as_deref(), Some("y"));
+    assert!(!keys[0].shifted.starts_sequence);
+    // Rules run from the start of the input, where X is 0
+    assert_eq!(keys[1].base.output.as_deref(), Some("q"));
+    assert_eq!(keys[1].shifted.output, None);
+}
+
+#[test]
+fn test_json() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let json = mim.keyboard_layout();
+    assert!(json.starts_with(concat!(
+        r#"{"states":[{"state":"init","keys":[{"base":{"key":"`","output":null,"#,
+        r#""starts_sequence":false,"next_state":null},"shifted":{"key":"~","#,
+    )));
+    assert!(json.contains(concat!(
+        r#"{"base":{"key":"k","output":"ক","starts_sequence":true,"#,
+        r#""next_state":"after-consonant"},"shifted":{"key":"K","output":null,"#,
+        r#""starts_sequence":false,"next_state":null}}"#,
+    )));
+    assert!(json.contains(r#"{"state":"after-consonant","keys":["#));
+    assert!(json.ends_with("}]}]}"));
+}
@@ -0,0 +1,66 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::keysym_to_char;
+
+const MIM: &str = r#"
+(input-method bn keyseq-test)
+(map
+  (trans
+   ((?ক) "ka")
+   ((0x0996) "kha")
+   ((#x0997 ?া) "gaa")
+   ((U0998) "gha")
+   ((U+0999 space) "nga ")
+   ((?\x099A) "ca")
+   ((parenleft ?\)) "()")
+   ("ছ" "cha")))
+(state
+  (init
+   (trans)))
+"#;
+
+#[test]
+fn test_non_ascii_keys() {
+    let mim = M17nMim::new(MIM);
+
+    assert_eq!(mim.convert("ক"), "ka");
+    assert_eq!(mim.convert("খ"), "kha");
+    assert_eq!(mim.convert("গা"), "gaa");
+    assert_eq!(mim.convert("ঘ"), "gha");
+    assert_eq!(mim.convert("ঙ "), "nga ");
+    assert_eq!(mim.convert("চ"), "ca");
+    assert_eq!(mim.convert("()"), "()");
+    assert_eq!(mim.convert("ছ"), "cha");
+
+    // Integer keys are character codes, not decimal text
+    assert_eq!(mim.convert("2453"), "2453");
+}
+
+#[test]
+fn test_keysym_to_char() {
+    assert_eq!(keysym_to_char("a"), Some('a'));
+    assert_eq!(keysym_to_char("ক"), Some('ক'));
+    assert_eq!(keysym_to_char("U0985"), Some('অ'));
+    assert_eq!(keysym_to_char("U+1F600"), Some('😀'));
+    assert_eq!(keysym_to_char("asciitilde"), Some('~'));
+    assert_eq!(keysym_to_char("Up"), None);
+    assert_eq!(keysym_to_char("C-a"), None);
+}
+
+#[test]
+fn test_long_keyseq() {
+    let mim = M17nMim::new(
+        r#"
+(input-method t long-keyseq)
+(map
+  (trans
+   ("abcdefghijkl" "twelve")
+   ("abc" "three")))
+(state
+  (init
+   (trans)))
+"#,
+    );
+
+    assert_eq!(mim.convert("abcdefghijkl"), "twelve");
+    assert_eq!(mim.convert("abcdefghijk"), "threedefghijk");
+}
@@ -0,0 +1,123 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::load_im_info_from_reader_with_limits;
+use m17n_mim_rs::lisp_parser::{
+    ParseErrorKind, ParseLimits, parse_mim, parse_mim_strict, parse_mim_with_limits,
+};
+
+#[test]
+fn test_deep_nesting_is_rejected() {
+    let input = "(".repeat(100_000);
+    let err = parse_mim_with_limits(&input, &ParseLimits::default()).unwrap_err();
+
+    assert_eq!(err.kind, ParseErrorKind::TooDeep);
+    assert_eq!(err.limit, 128);
+    assert_eq!((err.line, err.column), (1, 129));
+}
+
+#[test]
+fn test_new_keeps_what_precedes_the_limit() {
+    let input = format!(
+        "(input-method t deep)\n(title \"Deep\")\n{}",
+        "(".repeat(200)
+    );
+    let mim = M17nMim::new(&input);
+    assert_eq!(mim.get_name(), "deep");
+    assert_eq!(mim.get_title(), "Deep");
+    let err = mim.parse_error().unwrap();
+    assert_eq!(err.kind, ParseErrorKind::TooDeep);
+    assert_eq!(err.line, 3);
+
+    let err = M17nMim::with_limits(&input, &ParseLimits::default()).err();
+    assert_eq!(err.map(|err| err.kind), Some(ParseErrorKind::TooDeep));
+    let err = parse_mim(&input).err();
+    assert_eq!(err.map(|err| err.kind), Some(ParseErrorKind::TooDeep));
+
+    assert!(M17nMim::new("(input-method t shallow)").parse_error().is_none());
+}
+
+#[test]
+fn test_string_and_size_limits() {
+    let limits = ParseLimits {
+        max_string_len: 8,
+        ..ParseLimits::default()
+    };
+    let err = parse_mim_with_limits("(title\n \"0123456789\")", &limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::StringTooLong);
+    assert_eq!(err.line, 2);
+
+    let err = parse_mim_with_limits("(map (verylongmapname))", &limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::StringTooLong);
+
+    let limits = ParseLimits {
+        max_total_size: 16,
+        ..ParseLimits::default()
+    };
+    let err = parse_mim_with_limits("(input-method bn khipro)", &limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::TooLarge);
+}
+
+#[test]
+fn test_rule_limit() {
+    let mim = r#"
+        (input-method t rules)
+        (map (m ("a" "1") ("b" "2")) (n ("c" "3")))
+        (state (init (m) (n)))
+    "#;
+    let limits = ParseLimits {
+        max_rules: 3,
+        ..ParseLimits::default()
+    };
+    assert!(parse_mim_with_limits(mim, &limits).is_ok());
+
+    let limits = ParseLimits {
+        max_rules: 2,
+        ..ParseLimits::default()
+    };
+    let err = parse_mim_with_limits(mim, &limits).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::TooManyRules);
+    assert_eq!(err.line, 3);
+
+    let err = load_im_info_from_reader_with_limits(mim.as_bytes(), limits)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_m17n_mim_with_limits() {
+    let input = format!("(input-method t deep) {}", "(".repeat(1000));
+    let err = M17nMim::with_limits(&input, &ParseLimits::default())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind, ParseErrorKind::TooDeep);
+}
+
+#[test]
+fn test_duplicate_keyseq_does_not_panic() {
+    let mim =
+        M17nMim::new(r#"(input-method t dup) (map (m ("a" "1") ("a" "2"))) (state (init (m)))"#);
+    assert_eq!(mim.convert("a"), "2");
+}
+
+#[test]
+fn test_strict_parsing() {
+    let limits = ParseLimits::default();
+    let malformed = [
+        ("(map\n  (m (\"a\" \"1\"))", ParseErrorKind::UnclosedList, (1, 1)),
+        ("(input-method t x))", ParseErrorKind::UnexpectedClose, (1, 19)),
+        ("(title \"Bengali)", ParseErrorKind::UnterminatedString, (1, 8)),
+    ];
+
+    for (input, kind, position) in malformed {
+        // The lenient parser accepts what it can
+        assert!(parse_mim_with_limits(input, &limits).is_ok());
+
+        let err = parse_mim_strict(input, &limits).unwrap_err();
+        assert_eq!(err.kind, kind);
+        assert_eq!((err.line, err.column), position);
+    }
+
+    let err = parse_mim_strict("(a (b)", &limits).unwrap_err();
+    assert_eq!(err.to_string(), "unclosed list at line 1, column 1");
+    assert!(parse_mim_strict("(a ?( ?\\) \"(\") ; )", &limits).is_ok());
+}
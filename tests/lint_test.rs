@@ -0,0 +1,135 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::lint::*;
+use std::fs;
+
+fn lint_str(mim: &str) -> Vec<String> {
+    lint(M17nMim::new(mim).im_info())
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn test_fixtures() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/titlecase.mim").unwrap());
+    assert_eq!(lint(mim.im_info()), []);
+
+    // After a consonant, vowel keys type vowel signs
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let findings: Vec<_> = lint(mim.im_info())
+        .iter()
+        .map(|finding| (finding.code, finding.location.clone()))
+        .collect();
+    let expected = (
+        Code::ShadowedKeyseq,
+        Location::Branch("after-consonant".into(), 2),
+    );
+    assert_eq!(findings, vec![expected; 3]);
+}
+
+#[test]
+fn test_findings() {
+    let findings = lint_str(
+        r#"(input-method t lint)
+(map
+  (m ("a" "x") ("b" "y") ("a" "z") ("c" (beep)))
+  (n ("a" "w") ("d" (insert Y)))
+  (empty))
+(state
+  (init (m (shift second)) (n) (missing) (m))
+  (second (n (shift nowhere)) (nil))
+  (orphan (n)))"#,
+    );
+    assert_eq!(
+        findings,
+        [
+            "error[E001] state init, branch 2: map missing is not defined",
+            "error[E002] state second, branch 0: shift to state nowhere, which is not defined",
+            "warning[W001] state orphan: state orphan cannot be reached from state init",
+            "warning[W002] map n, rule 1: variable Y is read but never set, so it is always 0",
+            "warning[W003] map m, rule 0: a later rule of the map has the same KEYSEQ, so this one never matches",
+            "warning[W004] state init, branch 1: KEYSEQ \"a\" of map n is also matched by map m of branch 0, which takes precedence",
+            "warning[W004] state init, branch 3: branch 0 already uses map m, so this branch never matches",
+            "warning[W005] map empty: map empty has no rules",
+            "warning[W006] map m, rule 3: unknown action beep, which does nothing",
+        ]
+    );
+}
+
+#[test]
+fn test_codes_and_severity() {
+    let mim = M17nMim::new("(input-method t lint) (state (init (missing)))");
+    let findings = lint(mim.im_info());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].code, Code::UndefinedMap);
+    assert_eq!(findings[0].code.as_str(), "E001");
+    assert_eq!(findings[0].severity, Severity::Error);
+    assert_eq!(findings[0].location, Location::Branch("init".into(), 0));
+}
+
+#[test]
+fn test_source_positions() {
+    let findings: Vec<_> = lint_source(
+        r#"(input-method t lint)
+(map
+  (m ("a" "x")
+     ("a" "z"))
+  (empty))
+(state
+  (init "title" (m (shift second)) (missing))
+  (second (m)))
+; the title is not counted as a branch
+(map (empty ("b" "y")) (empty))"#,
+    )
+    .unwrap()
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+    assert_eq!(
+        findings,
+        [
+            "error[E001] state init, branch 1 (line 7, column 36): map missing is not defined",
+            "warning[W003] map m, rule 0 (line 3, column 6): a later rule of the map has the same KEYSEQ, so this one never matches",
+            "warning[W005] map empty (line 10, column 24): map empty has no rules",
+        ]
+    );
+
+    let findings = lint(M17nMim::new("(map (empty))").im_info());
+    assert_eq!(findings[0].position, None);
+}
+
+#[test]
+fn test_unsupported_pushback() {
+    let findings = lint_str(
+        r#"(input-method t lint)
+(map (m ("a" (pushback "b")) ("b" (pushback)) ("c" (pushback foo))))
+(state (init (m)))"#,
+    );
+    assert_eq!(
+        findings,
+        [
+            "warning[W007] map m, rule 0: pushback without a number of keys is not supported, so it does nothing",
+            "warning[W007] map m, rule 1: pushback without a number of keys is not supported, so it does nothing",
+            "warning[W007] map m, rule 2: pushback without a number of keys is not supported, so it does nothing",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_keyseqs() {
+    let mim = r#"(input-method t lint)
+(map (m ((0x110000) "x") ("a" "y") (("b" -1) "z")))
+(state (init (m)))"#;
+    assert_eq!(
+        lint_str(mim),
+        [
+            "warning[W008] map m, rule 0: a key code of the KEYSEQ is not a character, so the rule never matches",
+            "warning[W008] map m, rule 2: a key code of the KEYSEQ is not a character, so the rule never matches",
+        ]
+    );
+
+    let mim = M17nMim::new(mim);
+    let map = &mim.im_info().maps["m"];
+    assert_eq!(map.keyseqs(), [("a".to_string(), 1)]);
+    assert_eq!(map.invalid_keyseqs, [0, 2]);
+}
@@ -0,0 +1,129 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::context::*;
+use m17n_mim_rs::lint::*;
+use m17n_mim_rs::session::Session;
+
+fn loop_findings(mim: &M17nMim) -> Vec<String> {
+    lint(mim.im_info())
+        .iter()
+        .filter(|finding| finding.code == Code::InputLoop)
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn test_pushback_all_loops() {
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("a" (pushback 0)) ("b" "B")))
+(state (init (m)))"#,
+    );
+    let im_info = mim.im_info();
+
+    let err = try_convert(im_info, "ba").unwrap_err();
+    assert_eq!(
+        err,
+        LoopError {
+            steps: DEFAULT_STEP_BUDGET,
+            key: 'a',
+            state: "init".into(),
+        }
+    );
+
+    // convert and sessions commit the key instead of hanging
+    assert_eq!(convert(im_info, "bab"), "BaB");
+    let mut session = Session::new(im_info);
+    for key in "bab".chars() {
+        session.handle_key(im_info, key);
+    }
+    session.flush(im_info);
+    assert_eq!(session.take_committed(), "BaB");
+
+    assert_eq!(
+        loop_findings(&mim),
+        [
+            "error[E003] map m, rule 0: init -> init consumes no key, so the same key can be handled again forever"
+        ]
+    );
+}
+
+#[test]
+fn test_nil_shift_cycle() {
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("a" "A")) (n ("b" "B")))
+(state
+  (one (m) (nil (shift two)))
+  (two (n) (nil (shift one))))"#,
+    );
+    let im_info = mim.im_info();
+
+    assert_eq!(try_convert(im_info, "ab"), Ok("AB".into()));
+    let err = try_convert(im_info, "az").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "input method looped for 1000 steps without consuming key 'z' (in state one)"
+    );
+
+    // Reported once, not once per state of the cycle
+    assert_eq!(
+        loop_findings(&mim),
+        [
+            "error[E003] state one, branch 1: one -> two -> one consumes no key, so the same key can be handled again forever"
+        ]
+    );
+}
+
+#[test]
+fn test_step_budget() {
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("a" (pushback 0))))
+(state (init (m)))"#,
+    );
+    let im_info = mim.im_info();
+
+    let mut ctx = Context::new(im_info.initial_state());
+    ctx.step_budget = 3;
+    for _ in 0..3 {
+        assert_eq!(ctx.step(im_info, &['a']).unwrap().consumed(), 0);
+    }
+    assert_eq!(ctx.step(im_info, &['a']).unwrap_err().steps, 3);
+}
+
+#[test]
+fn test_pushback_without_loop() {
+    // Part of the KEYSEQ is handled again
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("ab" "X" (pushback 1)) ("b" "Y")))
+(state (init (m)))"#,
+    );
+    assert_eq!(try_convert(mim.im_info(), "ab"), Ok("XY".into()));
+    assert_eq!(loop_findings(&mim), Vec::<String>::new());
+
+    // All of it is handled again in another state, which consumes it
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("a" (pushback 0) (shift two))) (n ("a" "A")))
+(state (init (m)) (two (n (shift init))))"#,
+    );
+    assert_eq!(try_convert(mim.im_info(), "aa"), Ok("AA".into()));
+    assert_eq!(loop_findings(&mim), Vec::<String>::new());
+}
+
+#[test]
+fn test_nil_branch() {
+    let mim = M17nMim::new(
+        r#"(input-method t loop)
+(map (m ("a" "A")) (n ("z" "Z")))
+(state
+  (init (m) (nil (shift other)))
+  (other (n (shift init))))"#,
+    );
+    let im_info = mim.im_info();
+
+    // "z" matches nothing in init, so it is handled again in other
+    assert_eq!(try_convert(im_info, "za"), Ok("ZA".into()));
+    assert_eq!(loop_findings(&mim), Vec::<String>::new());
+}
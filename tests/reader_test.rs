@@ -0,0 +1,94 @@
+use m17n_mim_rs::im_info::{load_im_info, load_im_info_from_reader};
+use m17n_mim_rs::lisp_parser::{Element, SectionReader, parse_mim};
+use std::fs;
+use std::io::Cursor;
+
+#[test]
+fn test_reader_matches_str_loader() {
+    for path in [
+        "tests/fixtures/bn-sample.mim",
+        "tests/fixtures/titlecase.mim",
+    ] {
+        let mim = fs::read_to_string(path).unwrap();
+        let from_str = load_im_info(parse_mim(&mim).unwrap());
+        let from_reader = load_im_info_from_reader(Cursor::new(mim.as_bytes())).unwrap();
+
+        assert_eq!(from_reader.lang, from_str.lang);
+        assert_eq!(from_reader.name, from_str.name);
+        assert_eq!(from_reader.title, from_str.title);
+        assert_eq!(from_reader.description, from_str.description);
+        assert_eq!(from_reader.version, from_str.version);
+        assert_eq!(from_reader.maps.len(), from_str.maps.len());
+        for (name, map) in &from_str.maps {
+            assert_eq!(
+                from_reader.maps[name].fst.as_fst().as_bytes(),
+                map.fst.as_fst().as_bytes()
+            );
+        }
+        let state_names = |states: &[m17n_mim_rs::im_info::State]| {
+            states.iter().map(|s| s.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            state_names(&from_reader.states),
+            state_names(&from_str.states)
+        );
+    }
+}
+
+#[test]
+fn test_sections_are_split() {
+    let mim = r#"
+        (input-method bn test)
+        ; comment ( with a paren
+        (map (first ("a" "b")) (second ("c" "d")))
+        (state (init (first)) (other (second)))
+    "#;
+    let sections: Vec<Element> = SectionReader::new(mim.as_bytes())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let heads: Vec<String> = sections
+        .iter()
+        .map(|section| match section {
+            Element::List(list) => match list.as_slice() {
+                [Element::Symbol(head), Element::List(def), ..] => match &def[0] {
+                    Element::Symbol(name) => format!("{} {}", head, name),
+                    _ => head.clone(),
+                },
+                [Element::Symbol(head), ..] => head.clone(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        })
+        .collect();
+    assert_eq!(
+        heads,
+        [
+            "input-method",
+            "map first",
+            "map second",
+            "state init",
+            "state other"
+        ]
+    );
+}
+
+#[test]
+fn test_large_generated_mim() {
+    let mut mim = String::from("(input-method t large)\n(map\n (big\n");
+    for i in 0..20000 {
+        mim.push_str(&format!("  (\"k{}\" \"{}\")\n", i, i));
+    }
+    mim.push_str(" ))\n(state (init (big)))\n");
+
+    let im_info = load_im_info_from_reader(Cursor::new(mim.into_bytes())).unwrap();
+    assert_eq!(im_info.maps["big"].fst.len(), 20000);
+    assert_eq!(im_info.states.len(), 1);
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let data = b"(input-method bn test)\n(title \"\xff\")\n";
+    let result: Result<Vec<Element>, _> = SectionReader::new(&data[..]).collect();
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,108 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::context::convert;
+use m17n_mim_rs::reverse::*;
+use std::fs;
+
+const MIM: &str = r#"(input-method t reverse)
+(map
+  (consonant ("k" "ক") ("kh" "খ") ("g" "গ") ("x" "ক্ষ"))
+  (vowel ("i" "ি") ("a" "া") ("aa" "আ")))
+(state
+  (init (consonant) (vowel)))"#;
+
+fn found(mim: &M17nMim, target: &str) -> Vec<String> {
+    match keys_for(mim.im_info(), target, &SearchLimits::default()) {
+        KeysFor::Found(keys) => keys,
+        other => panic!("{target}: {other:?}"),
+    }
+}
+
+#[test]
+fn test_shortest_keys() {
+    let mim = M17nMim::new(MIM);
+    assert_eq!(found(&mim, "কি"), ["ki"]);
+    assert_eq!(found(&mim, "খা"), ["kha"]);
+    assert_eq!(found(&mim, "ক্ষ"), ["x"]);
+    // Keys that no rule has are typed as is
+    assert_eq!(found(&mim, "ক ি"), ["k i"]);
+    assert_eq!(found(&mim, ""), [""]);
+
+    for target in ["কি", "খা", "ক্ষ", "গআ"] {
+        for keys in found(&mim, target) {
+            assert_eq!(convert(mim.im_info(), &keys), target);
+        }
+    }
+}
+
+#[test]
+fn test_all_shortest_keys() {
+    let mim = M17nMim::new(
+        r#"(input-method t reverse)
+(map (m ("a" "ক") ("b" "ক") ("ab" "খ")))
+(state (init (m)))"#,
+    );
+    let mut keys = found(&mim, "কক");
+    keys.sort();
+    assert_eq!(keys, ["aa", "ba", "bb"]);
+    assert_eq!(found(&mim, "খ"), ["ab"]);
+
+    let limits = SearchLimits {
+        max_results: 2,
+        ..SearchLimits::default()
+    };
+    let KeysFor::Found(keys) = keys_for(mim.im_info(), "কক", &limits) else {
+        panic!();
+    };
+    assert_eq!(keys.len(), 2);
+}
+
+#[test]
+fn test_not_typeable() {
+    let mim = M17nMim::new(MIM);
+    let limits = SearchLimits::default();
+    assert_eq!(keys_for(mim.im_info(), "ঘ", &limits), KeysFor::NotTypeable);
+
+    let limits = SearchLimits {
+        max_keys: 2,
+        ..SearchLimits::default()
+    };
+    assert_eq!(
+        keys_for(mim.im_info(), "কিক", &limits),
+        KeysFor::NotTypeable
+    );
+    assert_eq!(
+        keys_for(mim.im_info(), "কি", &limits),
+        KeysFor::Found(vec!["ki".into()])
+    );
+}
+
+#[test]
+fn test_edited_text() {
+    // Text is deleted, so the preedit can't be used to cut inputs short
+    let mim = M17nMim::new(
+        r#"(input-method t reverse)
+(map (m ("k" "ক") ("q" (delete @-) "খ")))
+(state (init (m)))"#,
+    );
+    assert_eq!(found(&mim, "খ"), ["q"]);
+    assert_eq!(found(&mim, "কখ"), ["kkq"]);
+
+    let limits = SearchLimits {
+        max_states: 4,
+        ..SearchLimits::default()
+    };
+    assert_eq!(
+        keys_for(mim.im_info(), "খখ", &limits),
+        KeysFor::LimitReached
+    );
+}
+
+#[test]
+fn test_bengali_sample() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let limits = SearchLimits::default();
+    assert_eq!(found(&mim, "কি"), ["ki"]);
+    assert_eq!(found(&mim, "খা"), ["kha"]);
+    assert_eq!(found(&mim, "।"), ["."]);
+    assert_eq!(keys_for(mim.im_info(), "a", &limits), KeysFor::NotTypeable);
+}
@@ -0,0 +1,106 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::char_names::CharNames;
+use m17n_mim_rs::roundtrip::*;
+use std::fs;
+
+const MIM: &str = r#"(input-method t roundtrip)
+(map
+  (m ("k" "ক") ("K" "ক") ("kh" "খ") ("i" "ি") ("qqqq" "গ")))
+(state
+  (init (m)))"#;
+
+const BLOCK: std::ops::RangeInclusive<char> = '\u{995}'..='\u{998}';
+
+#[test]
+fn test_collisions() {
+    let mim = M17nMim::new(MIM);
+    let report = round_trip(
+        mim.im_info(),
+        BLOCK,
+        &CharNames::new(),
+        &RoundTripLimits::default(),
+    );
+    assert!(!report.truncated);
+
+    // "Ki" and "ki", "kK" and "kk" and so on only collide because "K" and
+    // "k" do
+    assert_eq!(
+        report.collisions,
+        [Collision {
+            output: "ক".into(),
+            inputs: vec!["K".into(), "k".into()],
+        }]
+    );
+}
+
+#[test]
+fn test_untypeable() {
+    let mim = M17nMim::new(MIM);
+    let report = round_trip(
+        mim.im_info(),
+        BLOCK,
+        &CharNames::new(),
+        &RoundTripLimits::default(),
+    );
+    assert_eq!(
+        report.untypeable,
+        [
+            // Typed with "qqqq", longer than the inputs tried
+            Untypeable {
+                ch: 'গ',
+                in_rules: true,
+            },
+            Untypeable {
+                ch: 'ঘ',
+                in_rules: false,
+            },
+        ]
+    );
+
+    let limits = RoundTripLimits {
+        max_keys: 4,
+        ..RoundTripLimits::default()
+    };
+    let report = round_trip(mim.im_info(), BLOCK, &CharNames::new(), &limits);
+    assert_eq!(report.untypeable.len(), 1);
+
+    // Code points without a name are taken to be unassigned
+    let mut names = CharNames::new();
+    names.insert('ক', "BENGALI LETTER KA");
+    names.insert('খ', "BENGALI LETTER KHA");
+    names.insert('গ', "BENGALI LETTER GA");
+    let report = round_trip(mim.im_info(), BLOCK, &names, &limits);
+    assert!(report.untypeable.is_empty());
+}
+
+#[test]
+fn test_limits() {
+    let mim = M17nMim::new(MIM);
+    let limits = RoundTripLimits {
+        max_keys: 3,
+        max_inputs: 5,
+    };
+    let report = round_trip(mim.im_info(), BLOCK, &CharNames::new(), &limits);
+    assert_eq!(report.inputs, 5);
+    assert!(report.truncated);
+    assert!(
+        report
+            .to_string()
+            .starts_with("5 inputs tried, stopped at the limit\n")
+    );
+}
+
+#[test]
+fn test_report_table() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let report = round_trip(
+        mim.im_info(),
+        '\u{995}'..='\u{998}',
+        &CharNames::new(),
+        &RoundTripLimits::default(),
+    );
+    let text = report.to_string();
+    assert!(text.contains("\nOutput  Code points    Inputs\n"));
+    assert!(text.contains("\n\"খ\"     U+0996         \"kh\", \"kho\"\n"));
+    assert!(text.ends_with("\nUntypeable  Character  In rules\nU+0998      ঘ          no\n"));
+}
@@ -0,0 +1,74 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::load_im_info;
+use m17n_mim_rs::lisp_parser::parse_mim;
+use m17n_mim_rs::session::Session;
+use std::fs;
+
+#[test]
+fn test_metadata() {
+    let mim = fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap();
+    let im_info = load_im_info(parse_mim(&mim).unwrap());
+
+    assert_eq!(im_info.version, "1.0.0");
+    assert_eq!(im_info.icon_file_name(), "bn-sample.png");
+    assert_eq!(im_info.state("init").unwrap().title, None);
+    assert_eq!(
+        im_info
+            .state("after-consonant")
+            .unwrap()
+            .title
+            .as_ref()
+            .unwrap()
+            .text,
+        "ক"
+    );
+    assert_eq!(im_info.state("after-consonant").unwrap().branches.len(), 4);
+}
+
+#[test]
+fn test_session_status_title() {
+    let mim = fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap();
+    let im_info = load_im_info(parse_mim(&mim).unwrap());
+    let mut session = Session::new(&im_info);
+
+    assert_eq!(session.status_title(&im_info).text, "Bengali Sample");
+
+    // "k" may still become "kh", so it is held back
+    session.handle_key(&im_info, 'k');
+    assert_eq!(session.preedit(), "k");
+    assert_eq!(session.current_state_name(&im_info), "init");
+
+    session.handle_key(&im_info, 'a');
+    assert_eq!(session.current_state_name(&im_info), "init");
+    assert_eq!(session.status_title(&im_info).text, "Bengali Sample");
+
+    session.handle_key(&im_info, 'k');
+    session.handle_key(&im_info, 'h');
+    assert_eq!(session.current_state_name(&im_info), "after-consonant");
+    assert_eq!(session.status_title(&im_info).text, "ক");
+
+    session.flush(&im_info);
+    assert_eq!(session.take_committed(), "কাখ");
+    assert_eq!(session.preedit(), "");
+}
+
+#[test]
+fn test_session_matches_convert() {
+    let mim_str = fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap();
+    let im_info = load_im_info(parse_mim(&mim_str).unwrap());
+    let mim = M17nMim::new(&mim_str);
+
+    for input in ["khamar", "kika.", "ami", "gokhn x"] {
+        let mut session = Session::new(&im_info);
+        for key in input.chars() {
+            session.handle_key(&im_info, key);
+        }
+        session.flush(&im_info);
+        assert_eq!(
+            session.take_committed(),
+            mim.convert(input),
+            "input: {}",
+            input
+        );
+    }
+}
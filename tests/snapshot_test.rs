@@ -0,0 +1,193 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::im_info::{FstData, ImInfo, load_im_info};
+use m17n_mim_rs::lisp_parser::parse_mim;
+use m17n_mim_rs::snapshot::*;
+use std::fs;
+use std::sync::Arc;
+
+const FIXTURES: [(&str, &str); 2] = [
+    ("tests/fixtures/bn-sample.mim", "kakhi gim. okar nr"),
+    (
+        "tests/fixtures/titlecase.mim",
+        "a quick brown fox iistanbul xiix",
+    ),
+];
+
+fn load_fixture(path: &str) -> ImInfo {
+    load_im_info(parse_mim(&fs::read_to_string(path).unwrap()).unwrap())
+}
+
+fn assert_same_im_info(a: &ImInfo, b: &ImInfo) {
+    assert_eq!(a.lang, b.lang);
+    assert_eq!(a.name, b.name);
+    assert_eq!(a.title, b.title);
+    assert_eq!(a.description, b.description);
+    assert_eq!(a.version, b.version);
+    assert_eq!(a.variables, b.variables);
+    assert_eq!(a.state_names, b.state_names);
+    assert_eq!(format!("{:?}", a.states), format!("{:?}", b.states));
+
+    assert_eq!(a.maps.len(), b.maps.len());
+    for (name, map) in &a.maps {
+        let other = &b.maps[name];
+        assert_eq!(map.rules, other.rules);
+        assert_eq!(map.invalid_keyseqs, other.invalid_keyseqs);
+        assert_eq!(map.fst.as_fst().as_bytes(), other.fst.as_fst().as_bytes());
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    for (path, input) in FIXTURES {
+        let im_info = load_fixture(path);
+        let bytes = write_snapshot(&im_info);
+        let loaded = load_snapshot(&bytes).unwrap();
+
+        assert_same_im_info(&im_info, &loaded);
+        assert_eq!(write_snapshot(&loaded), bytes);
+        assert_eq!(
+            M17nMim::from_im_info(loaded).convert(input),
+            M17nMim::from_im_info(im_info).convert(input)
+        );
+    }
+}
+
+#[test]
+fn test_zero_copy_loading() {
+    let im_info = load_fixture("tests/fixtures/bn-sample.mim");
+    let bytes: &'static [u8] = Vec::leak(write_snapshot(&im_info));
+
+    let loaded = load_snapshot_static(bytes).unwrap();
+    assert_same_im_info(&im_info, &loaded);
+    for map in loaded.maps.values() {
+        let FstData::Static(fst) = map.fst.as_fst().as_inner() else {
+            panic!("FST was copied");
+        };
+        assert!(bytes.as_ptr_range().contains(&fst.as_ptr()));
+    }
+
+    let loaded = load_snapshot_shared(Arc::new(bytes.to_vec())).unwrap();
+    assert_same_im_info(&im_info, &loaded);
+    assert!(
+        loaded
+            .maps
+            .values()
+            .all(|map| matches!(map.fst.as_fst().as_inner(), FstData::Shared { .. }))
+    );
+}
+
+#[test]
+fn test_load_snapshot_file() {
+    let mim = M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap());
+    let path = std::env::temp_dir().join(format!("m17n-snapshot-{}.bin", std::process::id()));
+    fs::write(&path, mim.to_snapshot()).unwrap();
+
+    // SAFETY: nothing else writes the file while it is mapped
+    let loaded = unsafe { load_snapshot_file(&path) }.unwrap();
+    assert_eq!(
+        M17nMim::from_im_info(loaded).convert("kakhi gim."),
+        mim.convert("kakhi gim.")
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_invalid_snapshots() {
+    let bytes = write_snapshot(&load_fixture("tests/fixtures/titlecase.mim"));
+
+    let err = load_snapshot(b"(input-method t titlecase)").unwrap_err();
+    assert_eq!(err.kind, SnapshotErrorKind::NotASnapshot);
+
+    let mut newer = bytes.clone();
+    newer[8] = 4;
+    let err = load_snapshot(&newer).unwrap_err();
+    assert_eq!(err.kind, SnapshotErrorKind::UnsupportedVersion(4));
+    assert_eq!(
+        err.to_string(),
+        "unsupported snapshot version 4 (expected 3)"
+    );
+
+    // Every truncation is an error rather than a panic
+    for len in 0..bytes.len() {
+        assert!(load_snapshot(&bytes[..len]).is_err());
+    }
+
+    let mut extended = bytes.clone();
+    extended.push(0);
+    assert_eq!(
+        load_snapshot(&extended).unwrap_err().kind,
+        SnapshotErrorKind::InvalidValue
+    );
+}
+
+#[test]
+fn test_corrupt_snapshots() {
+    let bytes = write_snapshot(&load_fixture("tests/fixtures/titlecase.mim"));
+
+    // A corrupt byte anywhere is rejected or loads an input method that
+    // still converts without panicking
+    for i in 0..bytes.len() {
+        let mut corrupt = bytes.clone();
+        corrupt[i] ^= 0x5a;
+        if let Ok(im_info) = load_snapshot(&corrupt) {
+            M17nMim::from_im_info(im_info).convert("a quick brown fox iistanbul");
+        }
+    }
+}
+
+#[test]
+fn test_inconsistent_snapshots() {
+    // A KEYSEQ leading past the end of the map's rules
+    let mut im_info = load_fixture("tests/fixtures/bn-sample.mim");
+    im_info.maps.get_mut("consonant").unwrap().rules.pop();
+    assert_eq!(
+        load_snapshot(&write_snapshot(&im_info)).unwrap_err().kind,
+        SnapshotErrorKind::InvalidFst
+    );
+
+    // A state stored under the id of another
+    let im_info = load_fixture("tests/fixtures/bn-sample.mim");
+    let mut bytes = write_snapshot(&im_info);
+    let second = &im_info.states[1];
+    let mut stored = second.id.to_le_bytes().to_vec();
+    stored.extend((second.name.len() as u32).to_le_bytes());
+    stored.extend(second.name.as_bytes());
+    let at = bytes
+        .windows(stored.len())
+        .position(|window| window == stored)
+        .unwrap();
+    bytes[at..at + 4].copy_from_slice(&im_info.states[0].id.to_le_bytes());
+    let err = load_snapshot(&bytes).unwrap_err();
+    assert_eq!(err.kind, SnapshotErrorKind::InvalidValue);
+    assert_eq!(err.offset, at);
+}
+
+#[test]
+fn test_state_defined_twice() {
+    let im_info = load_im_info(
+        parse_mim(
+            r#"(input-method t twice)
+(map (m ("a" "x")) (n ("a" "y")))
+(state (init (m)) (init (n)))"#,
+        )
+        .unwrap(),
+    );
+    assert_eq!(im_info.states.len(), 2);
+    let loaded = load_snapshot(&write_snapshot(&im_info)).unwrap();
+    assert_eq!(loaded.states.len(), 1);
+    assert_eq!(loaded.states[0].branches[0].map_name, "m");
+    assert_eq!(
+        M17nMim::from_im_info(loaded).convert("a"),
+        M17nMim::from_im_info(im_info).convert("a")
+    );
+}
+
+#[test]
+fn test_invalid_keyseqs_round_trip() {
+    let im_info = load_im_info(
+        parse_mim(r#"(input-method t invalid) (map (m ((0x110000) "x") ("a" "y")))"#).unwrap(),
+    );
+    let loaded = load_snapshot(&write_snapshot(&im_info)).unwrap();
+    assert_same_im_info(&im_info, &loaded);
+    assert_eq!(loaded.maps["m"].invalid_keyseqs, [0]);
+}
@@ -0,0 +1,57 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::stream::*;
+use std::fs;
+use std::io::{ErrorKind, Write};
+
+fn load_sample() -> M17nMim {
+    M17nMim::new(&fs::read_to_string("tests/fixtures/bn-sample.mim").unwrap())
+}
+
+#[test]
+fn test_stream_matches_convert() {
+    let mim = load_sample();
+    let input = "kakhi gim. ঌ okar nr k";
+
+    let output = convert_stream(mim.im_info(), input.as_bytes(), Vec::new()).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), mim.convert(input));
+
+    // Split anywhere, even inside a UTF-8 sequence
+    for split in 0..=input.len() {
+        let mut converter = StreamConverter::new(mim.im_info(), Vec::new());
+        converter.write_all(&input.as_bytes()[..split]).unwrap();
+        converter.write_all(&input.as_bytes()[split..]).unwrap();
+        let output = converter.finish().unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), mim.convert(input));
+    }
+}
+
+#[test]
+fn test_stream_writes_committed_text_early() {
+    let mim = load_sample();
+    let mut converter = StreamConverter::new(mim.im_info(), Vec::new());
+
+    converter.write_all(b"kakhi ").unwrap();
+    assert_eq!(converter.get_ref().as_slice(), "কাখি ".as_bytes());
+
+    // "k" may still become "kh", and "ক" may still take a vowel sign
+    converter.write_all(b"gim k").unwrap();
+    assert_eq!(converter.get_ref().as_slice(), "কাখি গিম ".as_bytes());
+
+    let output = converter.finish().unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "কাখি গিম ক");
+}
+
+#[test]
+fn test_stream_invalid_utf8() {
+    let mim = load_sample();
+
+    let mut converter = StreamConverter::new(mim.im_info(), Vec::new());
+    let err = converter.write_all(b"ka \xff").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(converter.get_ref().as_slice(), "কা ".as_bytes());
+
+    let mut converter = StreamConverter::new(mim.im_info(), Vec::new());
+    converter.write_all(&"ka ক".as_bytes()[..5]).unwrap();
+    let err = converter.finish().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
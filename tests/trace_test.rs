@@ -0,0 +1,152 @@
+use m17n_mim_rs::M17nMim;
+use m17n_mim_rs::trace::*;
+use std::fs;
+
+fn load(path: &str) -> M17nMim {
+    M17nMim::new(&fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn test_trace_steps() {
+    let mim = load("tests/fixtures/bn-sample.mim");
+    let trace = trace_conversion(mim.im_info(), "kha .");
+    assert_eq!(trace.output, mim.convert("kha ."));
+
+    let steps: Vec<_> = trace
+        .steps
+        .iter()
+        .map(|step| {
+            (
+                step.keys.as_str(),
+                step.state.as_str(),
+                step.matched
+                    .as_ref()
+                    .map(|(map, rule, _)| (map.as_str(), *rule)),
+                step.next_state.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            ("kh", "init", Some(("consonant", 1)), "after-consonant"),
+            ("a", "after-consonant", Some(("sign", 0)), "init"),
+            (" ", "init", None, "init"),
+            (".", "init", Some(("punct", 0)), "init"),
+        ]
+    );
+
+    let actions: Vec<_> = trace.steps[0]
+        .actions
+        .iter()
+        .map(|action| (action.source, action.action.as_str()))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            (ActionSource::Rule, "insert \"খ\""),
+            (ActionSource::Branch, "shift after-consonant"),
+        ]
+    );
+    assert_eq!(trace.steps[3].matched, Some(("punct".into(), 0, 2)));
+}
+
+#[test]
+fn test_trace_actions_and_variables() {
+    let mim = load("tests/fixtures/titlecase.mim");
+    let trace = trace_conversion(mim.im_info(), "ab");
+    assert_eq!(trace.output, "Ab");
+
+    let step = &trace.steps[1];
+    let actions: Vec<_> = step
+        .actions
+        .iter()
+        .map(|action| {
+            (
+                action.action.as_str(),
+                action.preedit_before.as_str(),
+                action.cursor_before,
+                action.preedit_after.as_str(),
+                action.cursor_after,
+            )
+        })
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            ("insert \"B\"", "A", 1, "AB", 2),
+            ("set X = 66", "AB", 2, "AB", 2),
+            ("delete @-", "AB", 2, "A", 1),
+            ("set X = 98", "A", 1, "A", 1),
+            ("insert X", "A", 1, "Ab", 2),
+        ]
+    );
+    assert_eq!(
+        step.variables,
+        [VarChange {
+            name: "X".into(),
+            before: 0,
+            after: 98,
+        }]
+    );
+}
+
+#[test]
+fn test_trace_text_and_json() {
+    let mim = load("tests/fixtures/bn-sample.mim");
+    assert_eq!(
+        mim.trace_text("ki "),
+        concat!(
+            "[0..1] \"k\" in init: consonant rule 0 (branch 0)\n",
+            "  rule   insert \"ক\"               \"|\" -> \"ক|\"\n",
+            "  branch shift after-consonant    \"ক|\" -> \"ক|\"\n",
+            "  now in after-consonant\n",
+            "[1..2] \"i\" in after-consonant: sign rule 1 (branch 0)\n",
+            "  rule   insert \"ি\"               \"ক|\" -> \"কি|\"\n",
+            "  branch shift init               \"কি|\" -> \"কি|\"\n",
+            "  now in init\n",
+            "[2..3] \" \" in init: no match, committed as is\n",
+            "output: \"কি \"\n",
+        )
+    );
+    assert_eq!(
+        mim.trace(" "),
+        concat!(
+            r#"{"input":" ","output":" ","steps":[{"input":[0,1],"keys":" ","state":"init","#,
+            r#""map":null,"rule":null,"branch":null,"nil_branch":null,"actions":[],"variables":[],"#,
+            r#""next_state":"init"}]}"#
+        )
+    );
+    assert!(mim.trace("k").contains(
+        r#"{"source":"rule","action":"insert \"ক\"","preedit_before":"","cursor_before":0,"preedit_after":"ক","cursor_after":1}"#
+    ));
+}
+
+#[test]
+fn test_trace_pushback_and_nil() {
+    let mim = M17nMim::new(
+        r#"(input-method t trace)
+(map (m ("ab" "X" (pushback 1)) ("b" "Y")) (n ("z" "Z")))
+(state
+  (init (m) (nil (shift other)))
+  (other (n (shift init))))"#,
+    );
+    assert_eq!(
+        mim.trace_text("abz"),
+        concat!(
+            "[0..1] \"ab\" in init: m rule 0 (branch 0), 1 pushed back\n",
+            "  rule   insert \"X\"               \"|\" -> \"X|\"\n",
+            "  rule   pushback 1               \"X|\" -> \"X|\"\n",
+            "[1..2] \"b\" in init: m rule 1 (branch 0)\n",
+            "  rule   insert \"Y\"               \"X|\" -> \"XY|\"\n",
+            "[2..2] \"z\" in init: no match, nil branch 1\n",
+            "  branch shift other              \"XY|\" -> \"XY|\"\n",
+            "  now in other\n",
+            "[2..3] \"z\" in other: n rule 0 (branch 0)\n",
+            "  rule   insert \"Z\"               \"XY|\" -> \"XYZ|\"\n",
+            "  branch shift init               \"XYZ|\" -> \"XYZ|\"\n",
+            "  now in init\n",
+            "output: \"XYZ\"\n",
+        )
+    );
+}